@@ -7,7 +7,10 @@ fn main() -> Result<()> {
     let builder = tonic_build::configure();
     builder
         .out_dir("src/pb")
-        .with_type_attributes(&["MaterializeRequest"], &[r#"#[derive(Eq, Hash)]"#])
+        .with_type_attributes(
+            &["MaterializeResponse.result", "ImportRow.row"],
+            &["#[allow(clippy::large_enum_variant)]"],
+        )
         .compile(
             &[
                 "../protos/metadata/messages.proto",
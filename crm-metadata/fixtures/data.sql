@@ -0,0 +1,9 @@
+INSERT INTO public.publishers (id, name, avatar) VALUES (10001, 'Lorna Hills', 'https://placehold.co/400x400');
+INSERT INTO public.publishers (id, name, avatar) VALUES (10002, 'Jarret Mraz', 'https://placehold.co/400x400');
+INSERT INTO public.publishers (id, name, avatar) VALUES (10003, 'Dewitt Kunde', 'https://placehold.co/400x400');
+INSERT INTO public.publishers (id, name, avatar) VALUES (10004, 'Ines Kovacek', 'https://placehold.co/400x400');
+INSERT INTO public.contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes) VALUES (1, 'Into the Wild Coast', 'A road trip along the coast.', '{10001,10002}', 'https://placehold.co/1600x900', 'https://placehold.co/1600x900', 'vlog', '2024-06-02 10:12:31.123456+08', 1203344, 45123, 1234);
+INSERT INTO public.contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes) VALUES (2, 'The Last Orbit', 'A crew fights to return home.', '{10003}', 'https://placehold.co/1600x900', 'https://placehold.co/1600x900', 'movie', '2024-05-18 21:40:02.654321+08', 8734521, 98234, 4521);
+INSERT INTO public.contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes) VALUES (3, 'Cat vs Cucumber', 'Thirty seconds of panic.', '{10004}', 'https://placehold.co/1600x900', 'https://placehold.co/1600x900', 'short', '2024-06-20 08:03:45.000001+08', 532101, 23012, 321);
+INSERT INTO public.contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes) VALUES (4, 'Dreams of Neon', 'Generated city lights at night.', '{10002}', 'https://placehold.co/1600x900', 'https://placehold.co/1600x900', 'ai_generated', '2024-04-11 13:30:00.5+08', 234512, 4512, 2310);
+INSERT INTO public.contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes) VALUES (5, 'Harbor Lights', 'A quiet story of a fishing town.', '{10003,10001}', 'https://placehold.co/1600x900', 'https://placehold.co/1600x900', 'movie', '2023-12-24 19:00:00+08', 3345123, 65234, 3412);
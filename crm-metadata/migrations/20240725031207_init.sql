@@ -0,0 +1,23 @@
+-- Add migration script here
+CREATE TYPE content_type AS ENUM('unspecified', 'short', 'vlog', 'movie', 'ai_generated');
+create table publishers(
+    id int NOT NULL PRIMARY KEY,
+    name varchar(128) NOT NULL,
+    avatar varchar(512) NOT NULL DEFAULT ''
+);
+create table contents(
+    id int NOT NULL PRIMARY KEY,
+    name varchar(256) NOT NULL,
+    description text NOT NULL DEFAULT '',
+    publisher_ids int[] NOT NULL DEFAULT '{}',
+    url varchar(512) NOT NULL DEFAULT '',
+    image varchar(512) NOT NULL DEFAULT '',
+    type content_type NOT NULL DEFAULT 'unspecified',
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    views bigint NOT NULL DEFAULT 0,
+    likes bigint NOT NULL DEFAULT 0,
+    dislikes bigint NOT NULL DEFAULT 0
+);
+create index contents_publisher_ids_idx on contents using GIN(publisher_ids);
+create index contents_type_idx on contents(type);
+create index contents_created_at_idx on contents(created_at);
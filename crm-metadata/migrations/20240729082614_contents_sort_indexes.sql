@@ -0,0 +1,6 @@
+-- Add migration script here
+-- keyset pagination orders by (sort key, id)
+create index contents_views_id_idx on contents(views desc, id desc);
+create index contents_likes_id_idx on contents(likes desc, id desc);
+create index contents_created_at_id_idx on contents(created_at desc, id desc);
+drop index contents_created_at_idx;
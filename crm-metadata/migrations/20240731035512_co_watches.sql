@@ -0,0 +1,8 @@
+-- Add migration script here
+-- how many users watched both contents, every pair is stored in both directions
+create table co_watches(
+    content_id int NOT NULL REFERENCES contents(id) ON DELETE CASCADE,
+    other_id int NOT NULL REFERENCES contents(id) ON DELETE CASCADE,
+    count bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (content_id, other_id)
+);
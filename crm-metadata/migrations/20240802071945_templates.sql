@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TYPE template_channel AS ENUM('unspecified', 'email', 'sms', 'in_app');
+create table templates(
+    name varchar(128) NOT NULL,
+    locale varchar(16) NOT NULL,
+    version int NOT NULL,
+    channel template_channel NOT NULL,
+    subject text NOT NULL,
+    text_body text NOT NULL,
+    html_body text NOT NULL DEFAULT '',
+    active boolean NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    published_at timestamptz,
+    PRIMARY KEY (name, locale, version)
+);
+-- at most one published version per name and locale
+create unique index templates_active_idx on templates(name, locale) WHERE active;
@@ -0,0 +1,2 @@
+-- Add migration script here
+alter table contents add column translations jsonb NOT NULL DEFAULT '{}';
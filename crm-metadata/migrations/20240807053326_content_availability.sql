@@ -0,0 +1,5 @@
+-- Add migration script here
+alter table contents
+    add column available_from timestamptz,
+    add column available_until timestamptz,
+    add column regions varchar(8)[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,20 @@
+-- Add migration script here
+-- engagement of the contents by the hour it was flushed in, trending ranks the recent hours
+create table content_engagement(
+    content_id int NOT NULL REFERENCES contents(id) ON DELETE CASCADE,
+    hour timestamptz NOT NULL,
+    views bigint NOT NULL DEFAULT 0,
+    likes bigint NOT NULL DEFAULT 0,
+    dislikes bigint NOT NULL DEFAULT 0,
+    PRIMARY KEY (content_id, hour)
+);
+
+create index content_engagement_hour_idx on content_engagement(hour);
+
+-- lower bound of the Wilson score interval of the like ratio at the z-score, small samples rank lower
+create function wilson_lower_bound(likes float8, dislikes float8, z float8) returns float8 as $$
+    SELECT CASE WHEN n <= 0 THEN 0
+        ELSE (p + z * z / (2 * n) - z * sqrt((p * (1 - p) + z * z / (4 * n)) / n)) / (1 + z * z / n)
+    END
+    FROM (SELECT likes + dislikes AS n, likes / nullif(likes + dislikes, 0) AS p) AS s
+$$ language sql immutable;
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- contents each viewer watched, a new watch of a viewer adds to the co_watches with the others
+create table viewer_watches(
+    viewer text NOT NULL,
+    content_id int NOT NULL REFERENCES contents(id) ON DELETE CASCADE,
+    PRIMARY KEY (viewer, content_id)
+);
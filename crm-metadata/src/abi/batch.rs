@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{BatchGetRequest, BatchGetResponse},
+    MetadataService, ServiceResult,
+};
+
+use super::{
+    content::{ContentModel, CONTENT_COLUMNS},
+    db_error,
+    mask::ContentMask,
+};
+
+const MAX_IDS: usize = 1000;
+
+impl MetadataService {
+    pub async fn batch_get(&self, req: BatchGetRequest) -> ServiceResult<BatchGetResponse> {
+        if req.ids.len() > MAX_IDS {
+            return Err(Status::invalid_argument(format!(
+                "At most {} ids are allowed",
+                MAX_IDS
+            )));
+        }
+        let mask = ContentMask::new(req.fields.as_ref())
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        // ids out of the range of the column are missing like any unknown id
+        let ids: Vec<i32> = req
+            .ids
+            .iter()
+            .filter_map(|id| i32::try_from(*id).ok())
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM contents WHERE id = ANY($1)",
+            CONTENT_COLUMNS
+        );
+        let rows = sqlx::query_as::<_, ContentModel>(&sql)
+            .bind(&ids)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        let contents =
+            ContentModel::into_contents_with(&self.pool, rows, mask.contains("publishers"))
+                .await
+                .map_err(db_error)?;
+        let mut contents: HashMap<_, _> = contents.into_iter().map(|c| (c.id, c)).collect();
+
+        let now = Utc::now();
+        let mut ret = BatchGetResponse::default();
+        let mut seen = HashSet::new();
+        for id in req.ids {
+            if !seen.insert(id) {
+                continue;
+            }
+            match contents.remove(&id) {
+                Some(c) if req.available_only && !c.is_available(now, &req.region) => {
+                    ret.unavailable_ids.push(id)
+                }
+                Some(c) => ret.contents.push(mask.apply(c)),
+                None => ret.missing_ids.push(id),
+            }
+        }
+        Ok(Response::new(ret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use prost_types::FieldMask;
+
+    #[tokio::test]
+    async fn batch_get_should_keep_the_request_order() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        sqlx::query("UPDATE contents SET available_until = '2024-07-01' WHERE id = 3")
+            .execute(&service.pool)
+            .await?;
+
+        let mut req = BatchGetRequest {
+            ids: vec![5, 404, 3, 1, 5, 2],
+            ..Default::default()
+        };
+        let ret = service.batch_get(req.clone()).await?.into_inner();
+        let ids: Vec<_> = ret.contents.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [5, 3, 1, 2]);
+        assert_eq!(ret.missing_ids, [404]);
+        assert!(ret.unavailable_ids.is_empty());
+
+        req.available_only = true;
+        let ret = service.batch_get(req.clone()).await?.into_inner();
+        let ids: Vec<_> = ret.contents.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [5, 1, 2]);
+        assert_eq!(ret.unavailable_ids, [3]);
+
+        req.fields = Some(FieldMask {
+            paths: vec!["name".to_string(), "image".to_string()],
+        });
+        let ret = service.batch_get(req.clone()).await?.into_inner();
+        assert!(ret.contents.iter().all(|c| c.publishers.is_empty()));
+        assert_eq!(ret.contents[0].name, "Harbor Lights");
+
+        req.fields = Some(FieldMask {
+            paths: vec!["title".to_string()],
+        });
+        let err = service.batch_get(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let req = BatchGetRequest {
+            ids: (1..=MAX_IDS as u32 + 1).collect(),
+            ..Default::default()
+        };
+        let err = service.batch_get(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+}
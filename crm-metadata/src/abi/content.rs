@@ -0,0 +1,615 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{
+        Content, ContentTranslation, ContentType, CreateContentRequest, DeleteContentRequest,
+        GetContentRequest, UpdateContentRequest,
+    },
+    MetadataService, ServiceResult,
+};
+
+use super::{db_error, publisher::PublisherModel};
+
+const MAX_NAME_LEN: usize = 256;
+const MAX_URL_LEN: usize = 512;
+const MAX_LOCALE_LEN: usize = 16;
+const MAX_REGION_LEN: usize = 8;
+
+pub(super) const CONTENT_COLUMNS: &str = "id, name, description, publisher_ids, url, image, type::text AS type, created_at, views, likes, dislikes, translations, available_from, available_until, regions";
+
+#[derive(FromRow, Debug, Clone)]
+pub(super) struct ContentModel {
+    pub(super) id: i32,
+    name: String,
+    description: String,
+    publisher_ids: Vec<i32>,
+    url: String,
+    image: String,
+    #[sqlx(rename = "type")]
+    content_type: String,
+    pub(super) created_at: DateTime<Utc>,
+    pub(super) views: i64,
+    pub(super) likes: i64,
+    pub(super) dislikes: i64,
+    translations: Json<HashMap<String, TranslationModel>>,
+    available_from: Option<DateTime<Utc>>,
+    available_until: Option<DateTime<Utc>>,
+    regions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TranslationModel {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+impl MetadataService {
+    pub async fn create_content(&self, req: CreateContentRequest) -> ServiceResult<Content> {
+        let content = req
+            .content
+            .ok_or_else(|| Status::invalid_argument("Content is required"))?;
+        let times =
+            validate_content(&content).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.check_publishers(&content).await?;
+
+        self.insert_content(&content, &times, false).await?;
+
+        self.get_content(GetContentRequest { id: content.id }).await
+    }
+
+    pub async fn update_content(&self, req: UpdateContentRequest) -> ServiceResult<Content> {
+        let content = req
+            .content
+            .ok_or_else(|| Status::invalid_argument("Content is required"))?;
+        let times =
+            validate_content(&content).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.check_publishers(&content).await?;
+
+        let ret = sqlx::query(
+            r#"UPDATE contents SET name = $2, description = $3, publisher_ids = $4, url = $5, image = $6, type = $7::content_type, translations = $8,
+            available_from = $9, available_until = $10, regions = $11
+            WHERE id = $1"#,
+        )
+        .bind(content.id as i32)
+        .bind(&content.name)
+        .bind(&content.description)
+        .bind(publisher_ids(&content))
+        .bind(&content.url)
+        .bind(&content.image)
+        .bind(type_name(content.r#type()))
+        .bind(translations(&content))
+        .bind(times.available_from)
+        .bind(times.available_until)
+        .bind(regions(&content))
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        if ret.rows_affected() == 0 {
+            return Err(not_found(content.id));
+        }
+
+        self.get_content(GetContentRequest { id: content.id }).await
+    }
+
+    pub async fn get_content(&self, req: GetContentRequest) -> ServiceResult<Content> {
+        match Content::load(&self.pool, req.id).await.map_err(db_error)? {
+            Some(content) => Ok(Response::new(content)),
+            None => Err(not_found(req.id)),
+        }
+    }
+
+    pub async fn delete_content(&self, req: DeleteContentRequest) -> ServiceResult<Content> {
+        let content = self
+            .get_content(GetContentRequest { id: req.id })
+            .await?
+            .into_inner();
+        sqlx::query("DELETE FROM contents WHERE id = $1")
+            .bind(req.id as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(Response::new(content))
+    }
+
+    /// insert the content, or update it like `update_content` if the id exists
+    pub(super) async fn upsert_content(&self, content: &Content) -> Result<(), Status> {
+        let times =
+            validate_content(content).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.check_publishers(content).await?;
+
+        self.insert_content(content, &times, true).await
+    }
+
+    /// insert the validated content, an existing id is updated like `update_content` if
+    /// upsert is set and is an error otherwise
+    async fn insert_content(
+        &self,
+        content: &Content,
+        times: &ContentTimes,
+        upsert: bool,
+    ) -> Result<(), Status> {
+        let on_conflict = if upsert {
+            r#"ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, description = EXCLUDED.description,
+                publisher_ids = EXCLUDED.publisher_ids, url = EXCLUDED.url, image = EXCLUDED.image,
+                type = EXCLUDED.type, translations = EXCLUDED.translations,
+                available_from = EXCLUDED.available_from, available_until = EXCLUDED.available_until,
+                regions = EXCLUDED.regions"#
+        } else {
+            ""
+        };
+        let sql = format!(
+            r#"INSERT INTO contents (id, name, description, publisher_ids, url, image, type, created_at, views, likes, dislikes, translations, available_from, available_until, regions)
+            VALUES ($1, $2, $3, $4, $5, $6, $7::content_type, $8, $9, $10, $11, $12, $13, $14, $15)
+            {}"#,
+            on_conflict
+        );
+        sqlx::query(&sql)
+            .bind(content.id as i32)
+            .bind(&content.name)
+            .bind(&content.description)
+            .bind(publisher_ids(content))
+            .bind(&content.url)
+            .bind(&content.image)
+            .bind(type_name(content.r#type()))
+            .bind(times.created_at.unwrap_or_else(Utc::now))
+            .bind(content.views as i64)
+            .bind(content.likes as i64)
+            .bind(content.dislikes as i64)
+            .bind(translations(content))
+            .bind(times.available_from)
+            .bind(times.available_until)
+            .bind(regions(content))
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// all the publishers referenced by the content must exist
+    async fn check_publishers(&self, content: &Content) -> Result<(), Status> {
+        let ids = publisher_ids(content);
+        let (count,): (i64,) = sqlx::query_as("SELECT count(*) FROM publishers WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        if count as usize != ids.len() {
+            return Err(Status::invalid_argument(format!(
+                "Unknown publishers in {:?}",
+                ids
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Content {
+    /// load the content with its publishers from the catalog
+    pub async fn load(pool: &PgPool, id: u32) -> sqlx::Result<Option<Self>> {
+        Self::load_with(pool, id, true).await
+    }
+
+    /// load the content, and its publishers only if asked for
+    pub(super) async fn load_with(
+        pool: &PgPool,
+        id: u32,
+        publishers: bool,
+    ) -> sqlx::Result<Option<Self>> {
+        // no content has an id out of the range of the column
+        let Ok(id) = i32::try_from(id) else {
+            return Ok(None);
+        };
+        let sql = format!("SELECT {} FROM contents WHERE id = $1", CONTENT_COLUMNS);
+        let content = sqlx::query_as::<_, ContentModel>(&sql)
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        let Some(content) = content else {
+            return Ok(None);
+        };
+
+        let contents = ContentModel::into_contents_with(pool, vec![content], publishers).await?;
+        Ok(contents.into_iter().next())
+    }
+
+    /// name and description in the first locale of the chain that has a translation,
+    /// see `locale_chain`
+    pub fn localized(&self, chain: &[String]) -> (&str, &str) {
+        let translation = chain.iter().find_map(|locale| {
+            self.translations
+                .iter()
+                .find(|(k, _)| k.replace('_', "-").eq_ignore_ascii_case(locale))
+                .map(|(_, t)| t)
+        });
+        match translation {
+            Some(t) if t.description.is_empty() => (&t.name, &self.description),
+            Some(t) => (&t.name, &t.description),
+            None => (&self.name, &self.description),
+        }
+    }
+
+    /// whether the content is within its licensing window at the time, and licensed in the
+    /// region unless the region is empty. An invalid bound makes the content unavailable
+    pub fn is_available(&self, at: DateTime<Utc>, region: &str) -> bool {
+        let started = self
+            .available_from
+            .as_ref()
+            .is_none_or(|ts| ts_to_utc(ts).is_ok_and(|from| from <= at));
+        let ended = self
+            .available_until
+            .as_ref()
+            .is_some_and(|ts| ts_to_utc(ts).map_or(true, |until| until <= at));
+        let licensed = region.is_empty()
+            || self.regions.is_empty()
+            || self.regions.iter().any(|r| r.eq_ignore_ascii_case(region));
+        started && !ended && licensed
+    }
+}
+
+impl ContentModel {
+    /// convert the rows to contents, loading all their publishers at once
+    pub(super) async fn into_contents(
+        pool: &PgPool,
+        rows: Vec<Self>,
+    ) -> sqlx::Result<Vec<Content>> {
+        Self::into_contents_with(pool, rows, true).await
+    }
+
+    /// like `into_contents`, but publishers are only loaded if asked for
+    pub(super) async fn into_contents_with(
+        pool: &PgPool,
+        rows: Vec<Self>,
+        publishers: bool,
+    ) -> sqlx::Result<Vec<Content>> {
+        let publishers = if publishers {
+            let mut publisher_ids: Vec<i32> = rows
+                .iter()
+                .flat_map(|c| c.publisher_ids.iter().copied())
+                .collect();
+            publisher_ids.sort_unstable();
+            publisher_ids.dedup();
+            sqlx::query_as::<_, PublisherModel>(
+                "SELECT id, name, avatar FROM publishers WHERE id = ANY($1)",
+            )
+            .bind(&publisher_ids)
+            .fetch_all(pool)
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(|c| c.into_content(&publishers))
+            .collect())
+    }
+
+    fn into_content(self, publishers: &[PublisherModel]) -> Content {
+        // keep the publishers in the order they are listed on the content
+        let publishers = self
+            .publisher_ids
+            .iter()
+            .filter_map(|id| publishers.iter().find(|p| p.id == *id))
+            .map(|p| p.clone().into_publisher())
+            .collect();
+
+        Content {
+            id: self.id as u32,
+            name: self.name,
+            description: self.description,
+            publishers,
+            url: self.url,
+            image: self.image,
+            r#type: type_from_name(&self.content_type) as i32,
+            created_at: Some(to_ts(self.created_at)),
+            views: self.views as u64,
+            likes: self.likes as u64,
+            dislikes: self.dislikes as u64,
+            translations: self
+                .translations
+                .0
+                .into_iter()
+                .map(|(locale, t)| {
+                    let t = ContentTranslation {
+                        name: t.name,
+                        description: t.description,
+                    };
+                    (locale, t)
+                })
+                .collect(),
+            available_from: self.available_from.map(to_ts),
+            available_until: self.available_until.map(to_ts),
+            regions: self.regions,
+        }
+    }
+}
+
+/// the timestamps of a content checked by `validate_content`
+struct ContentTimes {
+    created_at: Option<DateTime<Utc>>,
+    available_from: Option<DateTime<Utc>>,
+    available_until: Option<DateTime<Utc>>,
+}
+
+fn validate_content(content: &Content) -> Result<ContentTimes> {
+    if content.id == 0 || to_db_id(content.id).is_err() {
+        bail!("Content id must be positive and at most {}", i32::MAX);
+    }
+    if content.name.trim().is_empty() {
+        bail!("Content name is required");
+    }
+    if content.name.chars().count() > MAX_NAME_LEN {
+        bail!("Content name is longer than {} characters", MAX_NAME_LEN);
+    }
+    if content.url.len() > MAX_URL_LEN || content.image.len() > MAX_URL_LEN {
+        bail!("Content url and image must be within {} bytes", MAX_URL_LEN);
+    }
+    match ContentType::try_from(content.r#type) {
+        Ok(ContentType::Unspecified) | Err(_) => bail!("Invalid content type: {}", content.r#type),
+        Ok(_) => {}
+    }
+    for (locale, t) in &content.translations {
+        if locale.is_empty() || locale.len() > MAX_LOCALE_LEN {
+            bail!("Invalid translation locale: {:?}", locale);
+        }
+        if t.name.trim().is_empty() || t.name.chars().count() > MAX_NAME_LEN {
+            bail!("Invalid {} translation of the content name", locale);
+        }
+    }
+    let times = ContentTimes {
+        created_at: content.created_at.as_ref().map(ts_to_utc).transpose()?,
+        available_from: content.available_from.as_ref().map(ts_to_utc).transpose()?,
+        available_until: content
+            .available_until
+            .as_ref()
+            .map(ts_to_utc)
+            .transpose()?,
+    };
+    if let (Some(from), Some(until)) = (times.available_from, times.available_until) {
+        if from >= until {
+            bail!("Content must be available from before until");
+        }
+    }
+    for region in &content.regions {
+        if region.is_empty()
+            || region.len() > MAX_REGION_LEN
+            || !region.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            bail!("Invalid region: {:?}", region);
+        }
+    }
+    for publisher in &content.publishers {
+        to_db_id(publisher.id)?;
+    }
+    let ids = publisher_ids(content);
+    if (1..ids.len()).any(|i| ids[i..].contains(&ids[i - 1])) {
+        bail!("Duplicated publishers in {:?}", ids);
+    }
+    Ok(times)
+}
+
+fn publisher_ids(content: &Content) -> Vec<i32> {
+    content.publishers.iter().map(|p| p.id as i32).collect()
+}
+
+fn translations(content: &Content) -> Json<HashMap<String, TranslationModel>> {
+    let translations = content
+        .translations
+        .iter()
+        .map(|(locale, t)| {
+            let t = TranslationModel {
+                name: t.name.clone(),
+                description: t.description.clone(),
+            };
+            (locale.clone(), t)
+        })
+        .collect();
+    Json(translations)
+}
+
+/// regions are stored in upper case, e.g. cn is CN
+fn regions(content: &Content) -> Vec<String> {
+    content.regions.iter().map(|r| r.to_uppercase()).collect()
+}
+
+fn not_found(id: u32) -> Status {
+    Status::not_found(format!("Content not found: {}", id))
+}
+
+/// content_type label in postgres to ContentType, e.g. movie is CONTENT_TYPE_MOVIE
+fn type_from_name(name: &str) -> ContentType {
+    let name = format!("CONTENT_TYPE_{}", name.to_uppercase());
+    ContentType::from_str_name(&name).unwrap_or_default()
+}
+
+/// ContentType to content_type label in postgres, e.g. CONTENT_TYPE_MOVIE is movie
+pub(super) fn type_name(t: ContentType) -> String {
+    t.as_str_name()
+        .trim_start_matches("CONTENT_TYPE_")
+        .to_lowercase()
+}
+
+pub(super) fn to_ts(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// the timestamp as a date time, if it is within the range chrono supports
+pub(super) fn ts_to_utc(ts: &Timestamp) -> Result<DateTime<Utc>> {
+    let dt = u32::try_from(ts.nanos)
+        .ok()
+        .and_then(|nanos| Utc.timestamp_opt(ts.seconds, nanos).single());
+    match dt {
+        Some(dt) => Ok(dt),
+        None => bail!("Invalid timestamp: {}s {}ns", ts.seconds, ts.nanos),
+    }
+}
+
+/// the id as the int column it is stored in
+pub(super) fn to_db_id(id: u32) -> Result<i32> {
+    match i32::try_from(id) {
+        Ok(id) => Ok(id),
+        Err(_) => bail!("Id {} is greater than {}", id, i32::MAX),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{abi::locale_chain, pb::Publisher};
+
+    #[tokio::test]
+    async fn load_should_work() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let content = Content::load(&service.pool, 5).await?.unwrap();
+
+        assert_eq!(content.name, "Harbor Lights");
+        assert_eq!(content.r#type(), ContentType::Movie);
+        let publishers: Vec<_> = content.publishers.iter().map(|p| p.id).collect();
+        assert_eq!(publishers, [10003, 10001]);
+
+        assert!(Content::load(&service.pool, 404).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn content_crud_should_work() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut content = new_content(100);
+        let req = CreateContentRequest {
+            content: Some(content.clone()),
+        };
+        let created = service.create_content(req.clone()).await?.into_inner();
+        assert_eq!(created.name, "Night Train");
+        assert_eq!(created.publishers[0].name, "Lorna Hills");
+        assert_eq!(created.translations["zh-CN"].name, "夜行列车");
+        let chain = locale_chain("zh-cn");
+        assert_eq!(created.localized(&chain), ("夜行列车", ""));
+        let chain = locale_chain("fr");
+        assert_eq!(created.localized(&chain), ("Night Train", ""));
+
+        let err = service.create_content(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::AlreadyExists);
+
+        content.name = "Night Train II".to_string();
+        content.views = 42;
+        content.translations.clear();
+        let req = UpdateContentRequest {
+            content: Some(content),
+        };
+        let updated = service.update_content(req).await?.into_inner();
+        assert_eq!(updated.name, "Night Train II");
+        assert_eq!(updated.views, created.views);
+        assert!(updated.translations.is_empty());
+
+        let deleted = service
+            .delete_content(DeleteContentRequest { id: 100 })
+            .await?
+            .into_inner();
+        assert_eq!(deleted.id, 100);
+
+        let err = service
+            .get_content(GetContentRequest { id: 100 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_content_should_be_rejected() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut contents = vec![
+            new_content(0),
+            new_content(101),
+            new_content(102),
+            new_content(103),
+        ];
+        contents[1].r#type = ContentType::Unspecified as i32;
+        contents[2].publishers[0].id = 404;
+        contents[3].translations = [("fr".to_string(), ContentTranslation::default())].into();
+        contents.push(Content {
+            regions: vec!["C-N".to_string()],
+            ..new_content(104)
+        });
+        contents.push(new_content(i32::MAX as u32 + 1));
+        contents.push(Content {
+            created_at: Some(Timestamp {
+                seconds: i64::MAX,
+                nanos: 0,
+            }),
+            ..new_content(105)
+        });
+        contents.push(Content {
+            available_until: Some(Timestamp {
+                seconds: 0,
+                nanos: -1,
+            }),
+            ..new_content(106)
+        });
+
+        for content in contents {
+            let req = CreateContentRequest {
+                content: Some(content),
+            };
+            let err = service.create_content(req).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        }
+
+        let req = UpdateContentRequest {
+            content: Some(new_content(404)),
+        };
+        let err = service.update_content(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    #[test]
+    fn is_available_should_check_window_and_region() {
+        let now = Utc::now();
+        let mut content = new_content(1);
+        assert!(content.is_available(now, "CN"));
+
+        content.regions = vec!["US".to_string()];
+        assert!(content.is_available(now, ""));
+        assert!(content.is_available(now, "us"));
+        assert!(!content.is_available(now, "CN"));
+
+        content.available_until = Some(to_ts(now));
+        assert!(!content.is_available(now, ""));
+        content.available_until = None;
+        content.available_from = Some(to_ts(now + chrono::Duration::hours(1)));
+        assert!(!content.is_available(now, ""));
+    }
+
+    fn new_content(id: u32) -> Content {
+        Content {
+            id,
+            name: "Night Train".to_string(),
+            publishers: vec![Publisher {
+                id: 10001,
+                ..Default::default()
+            }],
+            r#type: ContentType::Movie as i32,
+            translations: [(
+                "zh-CN".to_string(),
+                ContentTranslation {
+                    name: "夜行列车".to_string(),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{Stream, StreamExt};
+use sqlx::{Postgres, Transaction};
+use tonic::{Response, Status};
+use tracing::warn;
+
+use crate::{
+    pb::{EngagementEvent, EngagementKind, RecordEngagementResponse},
+    MetadataService, ServiceResult,
+};
+
+const MAX_VIEWER_LEN: usize = 128;
+/// shorter intervals, zero included, are taken as this one
+const MIN_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Counters {
+    views: i64,
+    likes: i64,
+    dislikes: i64,
+}
+
+/// engagement counted in memory until the next flush
+#[derive(Debug, Default)]
+pub struct EngagementBuffer(Mutex<Pending>);
+
+#[derive(Debug, Default)]
+struct Pending {
+    /// by content id
+    counters: HashMap<i32, Counters>,
+    /// contents viewed by each viewer
+    watches: HashSet<(String, i32)>,
+}
+
+impl MetadataService {
+    pub async fn record_engagement(
+        &self,
+        mut stream: impl Stream<Item = Result<EngagementEvent, Status>> + Send + Unpin,
+    ) -> ServiceResult<RecordEngagementResponse> {
+        let mut ret = RecordEngagementResponse::default();
+        while let Some(event) = stream.next().await {
+            if self.engagement.add(&event?) {
+                ret.accepted += 1;
+            } else {
+                ret.rejected += 1;
+            }
+        }
+        Ok(Response::new(ret))
+    }
+
+    /// add the buffered engagement to the contents, returns the number of contents updated.
+    /// Engagement of unknown contents is dropped, and kept for the next flush on errors.
+    pub async fn flush_engagement(&self) -> sqlx::Result<u64> {
+        let pending = self.engagement.take();
+        if pending.counters.is_empty() {
+            return Ok(0);
+        }
+
+        let ret = self.write_engagement(&pending).await;
+        if ret.is_err() {
+            self.engagement.merge(pending);
+        }
+        ret
+    }
+
+    /// add the counters to the lifetime ones of the contents and to the current hour, and
+    /// the new watches to the co-watch counts
+    async fn write_engagement(&self, pending: &Pending) -> sqlx::Result<u64> {
+        let len = pending.counters.len();
+        let mut ids = Vec::with_capacity(len);
+        let mut views = Vec::with_capacity(len);
+        let mut likes = Vec::with_capacity(len);
+        let mut dislikes = Vec::with_capacity(len);
+        for (id, c) in &pending.counters {
+            ids.push(*id);
+            views.push(c.views);
+            likes.push(c.likes);
+            dislikes.push(c.dislikes);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let ret = sqlx::query(
+            r#"UPDATE contents AS c
+            SET views = c.views + e.views, likes = c.likes + e.likes, dislikes = c.dislikes + e.dislikes
+            FROM unnest($1::int[], $2::bigint[], $3::bigint[], $4::bigint[]) AS e(id, views, likes, dislikes)
+            WHERE c.id = e.id"#,
+        )
+        .bind(&ids)
+        .bind(&views)
+        .bind(&likes)
+        .bind(&dislikes)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"INSERT INTO content_engagement (content_id, hour, views, likes, dislikes)
+            SELECT e.id, date_trunc('hour', now()), e.views, e.likes, e.dislikes
+            FROM unnest($1::int[], $2::bigint[], $3::bigint[], $4::bigint[]) AS e(id, views, likes, dislikes)
+            JOIN contents c ON c.id = e.id
+            ON CONFLICT (content_id, hour) DO UPDATE
+            SET views = content_engagement.views + EXCLUDED.views,
+                likes = content_engagement.likes + EXCLUDED.likes,
+                dislikes = content_engagement.dislikes + EXCLUDED.dislikes"#,
+        )
+        .bind(&ids)
+        .bind(&views)
+        .bind(&likes)
+        .bind(&dislikes)
+        .execute(&mut *tx)
+        .await?;
+        write_co_watches(&mut tx, &pending.watches).await?;
+        tx.commit().await?;
+        Ok(ret.rows_affected())
+    }
+
+    /// flush the engagement every interval until the service is dropped. What is counted
+    /// after the last tick is lost unless the owner flushes on shutdown, like the server does
+    pub(crate) fn spawn_engagement_flush(&self, interval: Duration) {
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval.max(MIN_FLUSH_INTERVAL));
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                let svc = MetadataService { inner };
+                if let Err(e) = svc.flush_engagement().await {
+                    warn!("Failed to flush engagement: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// record the contents each viewer watched, a content watched for the first time by a viewer
+/// counts once as watched together with every other content the viewer watched
+async fn write_co_watches(
+    tx: &mut Transaction<'_, Postgres>,
+    watches: &HashSet<(String, i32)>,
+) -> sqlx::Result<()> {
+    if watches.is_empty() {
+        return Ok(());
+    }
+    let (viewers, ids): (Vec<_>, Vec<_>) = watches.iter().cloned().unzip();
+    let new: Vec<(String, i32)> = sqlx::query_as(
+        r#"INSERT INTO viewer_watches (viewer, content_id)
+        SELECT w.viewer, w.content_id FROM unnest($1::text[], $2::int[]) AS w(viewer, content_id)
+        JOIN contents c ON c.id = w.content_id
+        ON CONFLICT (viewer, content_id) DO NOTHING
+        RETURNING viewer, content_id"#,
+    )
+    .bind(&viewers)
+    .bind(&ids)
+    .fetch_all(&mut **tx)
+    .await?;
+    if new.is_empty() {
+        return Ok(());
+    }
+
+    // new watches pair with all the watches of the viewer, old ones only with the new ones
+    let (viewers, ids): (Vec<_>, Vec<_>) = new.into_iter().unzip();
+    sqlx::query(
+        r#"WITH new AS (SELECT * FROM unnest($1::text[], $2::int[]) AS n(viewer, content_id)),
+        seen AS (
+            SELECT w.viewer, w.content_id, (w.viewer, w.content_id) IN (SELECT * FROM new) AS is_new
+            FROM viewer_watches w WHERE w.viewer IN (SELECT viewer FROM new)
+        ),
+        pairs AS (
+            SELECT n.content_id, s.content_id AS other_id FROM new n
+            JOIN seen s ON s.viewer = n.viewer AND s.content_id <> n.content_id
+            UNION ALL
+            SELECT s.content_id, n.content_id AS other_id FROM new n
+            JOIN seen s ON s.viewer = n.viewer AND NOT s.is_new
+        )
+        INSERT INTO co_watches (content_id, other_id, count)
+        SELECT content_id, other_id, count(*) FROM pairs GROUP BY content_id, other_id
+        ON CONFLICT (content_id, other_id) DO UPDATE SET count = co_watches.count + EXCLUDED.count"#,
+    )
+    .bind(&viewers)
+    .bind(&ids)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+impl EngagementBuffer {
+    /// false if the event has no valid content id or no kind, or its viewer is too long
+    fn add(&self, event: &EngagementEvent) -> bool {
+        let kind = event.kind();
+        let id = match i32::try_from(event.content_id) {
+            Ok(id) if id > 0 && kind != EngagementKind::Unspecified => id,
+            _ => return false,
+        };
+        if event.viewer.len() > MAX_VIEWER_LEN {
+            return false;
+        }
+        let count = event.count.max(1) as i64;
+
+        let mut pending = self.0.lock().unwrap();
+        let c = pending.counters.entry(id).or_default();
+        match kind {
+            EngagementKind::View => c.views += count,
+            EngagementKind::Like => c.likes += count,
+            EngagementKind::Dislike => c.dislikes += count,
+            EngagementKind::Unspecified => unreachable!(),
+        }
+        if kind == EngagementKind::View && !event.viewer.is_empty() {
+            pending.watches.insert((event.viewer.clone(), id));
+        }
+        true
+    }
+
+    fn take(&self) -> Pending {
+        mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    fn merge(&self, other: Pending) {
+        let mut pending = self.0.lock().unwrap();
+        for (id, o) in other.counters {
+            let c = pending.counters.entry(id).or_default();
+            c.views += o.views;
+            c.likes += o.likes;
+            c.dislikes += o.dislikes;
+        }
+        pending.watches.extend(other.watches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::Content;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn engagement_should_be_flushed_in_batches() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let before = Content::load(&service.pool, 2).await?.unwrap();
+
+        let events = vec![
+            event(2, EngagementKind::View, 0),
+            event(2, EngagementKind::View, 10),
+            event(2, EngagementKind::Like, 1),
+            event(2, EngagementKind::Dislike, 2),
+            event(404, EngagementKind::View, 1),
+            event(2, EngagementKind::Unspecified, 1),
+            event(0, EngagementKind::View, 1),
+        ];
+        let stream = tokio_stream::iter(events).map(Ok);
+        let ret = service.record_engagement(stream).await?.into_inner();
+        assert_eq!(ret.accepted, 5);
+        assert_eq!(ret.rejected, 2);
+
+        // nothing is written until the flush
+        let content = Content::load(&service.pool, 2).await?.unwrap();
+        assert_eq!(content.views, before.views);
+
+        // the unknown content is dropped
+        assert_eq!(service.flush_engagement().await?, 1);
+        let content = Content::load(&service.pool, 2).await?.unwrap();
+        assert_eq!(content.views, before.views + 11);
+        assert_eq!(content.likes, before.likes + 1);
+        assert_eq!(content.dislikes, before.dislikes + 2);
+        let hourly: (i64, i64, i64) = sqlx::query_as(
+            "SELECT views, likes, dislikes FROM content_engagement WHERE content_id = 2",
+        )
+        .fetch_one(&service.pool)
+        .await?;
+        assert_eq!(hourly, (11, 1, 2));
+
+        assert_eq!(service.flush_engagement().await?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn views_of_a_viewer_should_count_as_co_watches() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        // watching a content again or liking it is not a new watch
+        let batches = [
+            vec![view(1, "a"), view(2, "a"), view(2, "a"), view(404, "a")],
+            vec![
+                view(3, "a"),
+                view(1, "a"),
+                event(2, EngagementKind::Like, 1),
+                view(2, "b"),
+                view(3, "b"),
+                view(4, ""),
+            ],
+        ];
+        for events in batches {
+            let stream = tokio_stream::iter(events).map(Ok);
+            service.record_engagement(stream).await?;
+            service.flush_engagement().await?;
+        }
+
+        let co_watches: Vec<(i32, i32, i64)> = sqlx::query_as(
+            "SELECT content_id, other_id, count FROM co_watches ORDER BY content_id, other_id",
+        )
+        .fetch_all(&service.pool)
+        .await?;
+        assert_eq!(
+            co_watches,
+            [
+                (1, 2, 1),
+                (1, 3, 1),
+                (2, 1, 1),
+                (2, 3, 2),
+                (3, 1, 1),
+                (3, 2, 2)
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn engagement_should_be_flushed_periodically() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        // a zero interval must not panic the ticker
+        service.spawn_engagement_flush(Duration::ZERO);
+        let stream = tokio_stream::iter(vec![view(2, "")]).map(Ok);
+        service.record_engagement(stream).await?;
+
+        let mut flushed = None;
+        for _ in 0..50 {
+            flushed =
+                sqlx::query_scalar("SELECT views FROM content_engagement WHERE content_id = 2")
+                    .fetch_optional(&service.pool)
+                    .await?;
+            if flushed.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(flushed, Some(1i64));
+        Ok(())
+    }
+
+    fn event(content_id: u32, kind: EngagementKind, count: u32) -> EngagementEvent {
+        EngagementEvent {
+            content_id,
+            kind: kind as i32,
+            count,
+            ..Default::default()
+        }
+    }
+
+    fn view(content_id: u32, viewer: &str) -> EngagementEvent {
+        EngagementEvent {
+            viewer: viewer.to_string(),
+            ..event(content_id, EngagementKind::View, 1)
+        }
+    }
+}
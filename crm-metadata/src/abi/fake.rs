@@ -0,0 +1,57 @@
+use chrono::{DateTime, Days, Utc};
+use fake::{
+    faker::{chrono::en::DateTimeBetween, lorem::en::Sentence, name::en::Name},
+    Fake,
+};
+use prost_types::Timestamp;
+use rand::Rng;
+
+use crate::pb::{Content, Publisher};
+
+impl Content {
+    /// made up content for demos, the same id gives different content each time
+    pub fn fake(id: u32) -> Self {
+        let mut rng = rand::thread_rng();
+        Content {
+            id,
+            name: Name().fake(),
+            description: Sentence(3..7).fake(),
+            publishers: (1..rng.gen_range(2..10))
+                .map(|_| Publisher::fake())
+                .collect(),
+            url: "https://placehold.co/1600x900".to_string(),
+            image: "https://placehold.co/1600x900".to_string(),
+            r#type: rng.gen_range(1..5),
+            created_at: created_at(),
+            views: rng.gen_range(123432..10000000),
+            likes: rng.gen_range(1234..100000),
+            dislikes: rng.gen_range(123..10000),
+            translations: Default::default(),
+            available_from: None,
+            available_until: None,
+            regions: vec![],
+        }
+    }
+}
+
+impl Publisher {
+    pub fn fake() -> Self {
+        Publisher {
+            id: (10000..2000000).fake(),
+            name: Name().fake(),
+            avatar: "https://placehold.co/400x400".to_string(),
+        }
+    }
+}
+
+fn before(days: u64) -> DateTime<Utc> {
+    Utc::now().checked_sub_days(Days::new(days)).unwrap()
+}
+
+fn created_at() -> Option<Timestamp> {
+    let date: DateTime<Utc> = DateTimeBetween(before(365), before(0)).fake();
+    Some(Timestamp {
+        seconds: date.timestamp(),
+        nanos: date.timestamp_subsec_nanos() as i32,
+    })
+}
@@ -0,0 +1,100 @@
+use futures::{Stream, StreamExt};
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{import_row::Row, ImportContentsResponse, ImportError, ImportRow},
+    MetadataService, ServiceResult,
+};
+
+impl MetadataService {
+    pub async fn import_contents(
+        &self,
+        mut stream: impl Stream<Item = Result<ImportRow, Status>> + Send + Unpin,
+    ) -> ServiceResult<ImportContentsResponse> {
+        let mut ret = ImportContentsResponse::default();
+        let mut row = 0;
+        while let Some(req) = stream.next().await {
+            row += 1;
+            let res = match req?.row {
+                Some(Row::Publisher(publisher)) => self
+                    .upsert_publisher(&publisher)
+                    .await
+                    .map(|_| ret.publishers += 1),
+                Some(Row::Content(content)) => self
+                    .upsert_content(&content)
+                    .await
+                    .map(|_| ret.contents += 1),
+                None => Err(Status::invalid_argument("Empty row")),
+            };
+            if let Err(status) = res {
+                ret.errors.push(ImportError {
+                    row,
+                    message: status.message().to_string(),
+                });
+            }
+        }
+        Ok(Response::new(ret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::{Content, ContentType, GetContentRequest, Publisher};
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn import_contents_should_upsert_and_report_bad_rows() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let publisher = Publisher {
+            id: 20001,
+            name: "Mae Runolfsson".to_string(),
+            ..Default::default()
+        };
+        let content = Content {
+            id: 2,
+            name: "The Last Orbit (Director's Cut)".to_string(),
+            publishers: vec![publisher.clone()],
+            r#type: ContentType::Movie as i32,
+            ..Default::default()
+        };
+        let rows = vec![
+            Row::Publisher(publisher),
+            Row::Content(content.clone()),
+            Row::Content(Content {
+                id: 100,
+                r#type: ContentType::Unspecified as i32,
+                ..content.clone()
+            }),
+            Row::Content(Content {
+                id: 101,
+                publishers: vec![Publisher {
+                    id: 404,
+                    ..Default::default()
+                }],
+                ..content
+            }),
+        ];
+        let rows: Vec<_> = rows
+            .into_iter()
+            .map(|row| ImportRow { row: Some(row) })
+            .collect();
+        let stream = tokio_stream::iter(rows).map(Ok);
+
+        let ret = service.import_contents(stream).await?.into_inner();
+        assert_eq!(ret.publishers, 1);
+        assert_eq!(ret.contents, 1);
+        let rows: Vec<_> = ret.errors.iter().map(|e| e.row).collect();
+        assert_eq!(rows, [3, 4]);
+
+        let content = service
+            .get_content(GetContentRequest { id: 2 })
+            .await?
+            .into_inner();
+        assert_eq!(content.name, "The Last Orbit (Director's Cut)");
+        assert_eq!(content.publishers[0].name, "Mae Runolfsson");
+        // counters are left untouched
+        assert_eq!(content.views, 8734521);
+        Ok(())
+    }
+}
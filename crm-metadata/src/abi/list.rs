@@ -0,0 +1,298 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{ContentSort, ContentType, ListContentsRequest, ListContentsResponse},
+    MetadataService, ServiceResult,
+};
+
+use super::{
+    content::{to_db_id, ts_to_utc, type_name, ContentModel, CONTENT_COLUMNS},
+    db_error,
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// position of the last content of a page, contents are ordered by (sort key, id) descending
+#[derive(Debug, PartialEq)]
+struct Cursor {
+    sort: ContentSort,
+    key: i64,
+    id: i32,
+}
+
+impl MetadataService {
+    pub async fn list_contents(
+        &self,
+        req: ListContentsRequest,
+    ) -> ServiceResult<ListContentsResponse> {
+        let sort = ContentSort::try_from(req.sort)
+            .map_err(|_| Status::invalid_argument(format!("Invalid sort: {}", req.sort)))?;
+        let page_size = match req.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            n => n.min(MAX_PAGE_SIZE),
+        } as usize;
+        let cursor = match req.cursor.as_str() {
+            "" => None,
+            s => {
+                Some(Cursor::decode(s, sort).map_err(|e| Status::invalid_argument(e.to_string()))?)
+            }
+        };
+
+        let mut qb = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM contents WHERE TRUE",
+            CONTENT_COLUMNS
+        ));
+        if !req.types.is_empty() {
+            let types =
+                type_names(&req.types).map_err(|e| Status::invalid_argument(e.to_string()))?;
+            qb.push(" AND type = ANY(")
+                .push_bind(types)
+                .push("::content_type[])");
+        }
+        if !req.publisher_ids.is_empty() {
+            let ids = req
+                .publisher_ids
+                .iter()
+                .map(|id| to_db_id(*id))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            qb.push(" AND publisher_ids && ").push_bind(ids);
+        }
+        if let Some(after) = &req.created_after {
+            let after = ts_to_utc(after).map_err(|e| Status::invalid_argument(e.to_string()))?;
+            qb.push(" AND created_at >= ").push_bind(after);
+        }
+        if let Some(before) = &req.created_before {
+            let before = ts_to_utc(before).map_err(|e| Status::invalid_argument(e.to_string()))?;
+            qb.push(" AND created_at <= ").push_bind(before);
+        }
+        if req.available_only {
+            qb.push(" AND (available_from IS NULL OR available_from <= now())")
+                .push(" AND (available_until IS NULL OR available_until > now())");
+            if !req.region.is_empty() {
+                qb.push(" AND (regions = '{}' OR ")
+                    .push_bind(req.region.to_uppercase())
+                    .push(" = ANY(regions))");
+            }
+        }
+
+        let column = sort_column(sort);
+        if let Some(cursor) = cursor {
+            qb.push(format!(" AND ({}, id) < (", column));
+            match sort {
+                ContentSort::CreatedAt => {
+                    let key = micros_to_utc(cursor.key)
+                        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                    qb.push_bind(key)
+                }
+                ContentSort::Views | ContentSort::Likes => qb.push_bind(cursor.key),
+            };
+            qb.push(", ").push_bind(cursor.id).push(")");
+        }
+        // one more row tells whether there is a next page
+        qb.push(format!(" ORDER BY {} DESC, id DESC LIMIT ", column))
+            .push_bind(page_size as i64 + 1);
+
+        let mut rows: Vec<ContentModel> = qb
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+        let next_cursor = if rows.len() > page_size {
+            rows.truncate(page_size);
+            rows.last().map(|c| Cursor::after(sort, c).encode())
+        } else {
+            None
+        };
+
+        let contents = ContentModel::into_contents(&self.pool, rows)
+            .await
+            .map_err(db_error)?;
+        Ok(Response::new(ListContentsResponse {
+            contents,
+            next_cursor: next_cursor.unwrap_or_default(),
+        }))
+    }
+}
+
+impl Cursor {
+    fn after(sort: ContentSort, content: &ContentModel) -> Self {
+        let key = match sort {
+            ContentSort::CreatedAt => content.created_at.timestamp_micros(),
+            ContentSort::Views => content.views,
+            ContentSort::Likes => content.likes,
+        };
+        Self {
+            sort,
+            key,
+            id: content.id,
+        }
+    }
+
+    fn encode(&self) -> String {
+        format!("{}.{}.{}", self.sort as i32, self.key, self.id)
+    }
+
+    fn decode(s: &str, sort: ContentSort) -> Result<Self> {
+        let invalid = || anyhow!("Invalid cursor: {}", s);
+        let mut parts = s.split('.');
+        let (Some(cursor_sort), Some(key), Some(id), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        // a cursor is only meaningful for the sort it was created with
+        if cursor_sort.parse::<i32>().map_err(|_| invalid())? != sort as i32 {
+            bail!("Cursor does not match the sort order");
+        }
+
+        Ok(Self {
+            sort,
+            key: key.parse().map_err(|_| invalid())?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+fn sort_column(sort: ContentSort) -> &'static str {
+    match sort {
+        ContentSort::CreatedAt => "created_at",
+        ContentSort::Views => "views",
+        ContentSort::Likes => "likes",
+    }
+}
+
+fn type_names(types: &[i32]) -> Result<Vec<String>> {
+    types
+        .iter()
+        .map(|t| match ContentType::try_from(*t) {
+            Ok(ContentType::Unspecified) | Err(_) => Err(anyhow!("Invalid content type: {}", t)),
+            Ok(t) => Ok(type_name(t)),
+        })
+        .collect()
+}
+
+fn micros_to_utc(micros: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros)
+        .ok_or_else(|| anyhow!("Invalid cursor time: {}", micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_should_round_trip() -> Result<()> {
+        let cursor = Cursor {
+            sort: ContentSort::Likes,
+            key: 45123,
+            id: 1,
+        };
+        let s = cursor.encode();
+        assert_eq!(Cursor::decode(&s, ContentSort::Likes)?, cursor);
+
+        assert!(Cursor::decode(&s, ContentSort::Views).is_err());
+        assert!(Cursor::decode("2.45123", ContentSort::Likes).is_err());
+        assert!(Cursor::decode("2.x.1", ContentSort::Likes).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_contents_should_filter_and_sort() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+
+        let req = ListContentsRequest {
+            types: vec![ContentType::Movie as i32],
+            sort: ContentSort::Likes as i32,
+            ..Default::default()
+        };
+        let ret = service.list_contents(req).await?.into_inner();
+        assert_eq!(ids(&ret), [2, 5]);
+        assert!(ret.next_cursor.is_empty());
+
+        let req = ListContentsRequest {
+            publisher_ids: vec![10001],
+            created_after: Some(prost_types::Timestamp {
+                // 2024-01-01
+                seconds: 1704067200,
+                nanos: 0,
+            }),
+            ..Default::default()
+        };
+        let ret = service.list_contents(req).await?.into_inner();
+        assert_eq!(ids(&ret), [1]);
+        assert_eq!(ret.contents[0].publishers.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_contents_should_skip_unavailable() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        sqlx::query("UPDATE contents SET available_until = '2024-07-01' WHERE id = 3")
+            .execute(&service.pool)
+            .await?;
+        sqlx::query("UPDATE contents SET regions = '{US}' WHERE id = 1")
+            .execute(&service.pool)
+            .await?;
+
+        let mut req = ListContentsRequest {
+            available_only: true,
+            ..Default::default()
+        };
+        let ret = service.list_contents(req.clone()).await?.into_inner();
+        assert_eq!(ids(&ret), [1, 2, 4, 5]);
+
+        req.region = "cn".to_string();
+        let ret = service.list_contents(req).await?.into_inner();
+        assert_eq!(ids(&ret), [2, 4, 5]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_contents_should_paginate() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut all = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let req = ListContentsRequest {
+                page_size: 2,
+                cursor,
+                ..Default::default()
+            };
+            let ret = service.list_contents(req).await?.into_inner();
+            all.extend(ids(&ret));
+            if ret.next_cursor.is_empty() {
+                break;
+            }
+            cursor = ret.next_cursor;
+        }
+        // newest first
+        assert_eq!(all, [3, 1, 2, 4, 5]);
+
+        let req = ListContentsRequest {
+            cursor: "garbage".to_string(),
+            ..Default::default()
+        };
+        let err = service.list_contents(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let req = ListContentsRequest {
+            created_after: Some(prost_types::Timestamp {
+                seconds: i64::MIN,
+                nanos: 0,
+            }),
+            ..Default::default()
+        };
+        let err = service.list_contents(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    fn ids(ret: &ListContentsResponse) -> Vec<u32> {
+        ret.contents.iter().map(|c| c.id).collect()
+    }
+}
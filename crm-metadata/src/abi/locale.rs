@@ -0,0 +1,36 @@
+use super::template::DEFAULT_LOCALE;
+
+/// locales to try in order, from the most specific one to the default locale,
+/// e.g. zh-CN gives zh-cn, zh and en. Locales are compared case-insensitively.
+pub fn locale_chain(locale: &str) -> Vec<String> {
+    let locale = locale.trim().replace('_', "-").to_lowercase();
+    let mut chain = Vec::new();
+    let mut rest = locale.as_str();
+    while !rest.is_empty() {
+        chain.push(rest.to_string());
+        rest = match rest.rfind('-') {
+            Some(i) => &rest[..i],
+            None => "",
+        };
+    }
+    if !chain.iter().any(|l| l == DEFAULT_LOCALE) {
+        chain.push(DEFAULT_LOCALE.to_string());
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_chain_should_work() {
+        assert_eq!(locale_chain("zh-CN"), ["zh-cn", "zh", "en"]);
+        assert_eq!(
+            locale_chain("zh_Hant_TW"),
+            ["zh-hant-tw", "zh-hant", "zh", "en"]
+        );
+        assert_eq!(locale_chain("en-GB"), ["en-gb", "en"]);
+        assert_eq!(locale_chain(""), ["en"]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use prost_types::FieldMask;
+
+use crate::pb::Content;
+
+/// top level fields of Content that can be masked, id is always returned
+const CONTENT_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "description",
+    "publishers",
+    "url",
+    "image",
+    "type",
+    "created_at",
+    "views",
+    "likes",
+    "dislikes",
+    "translations",
+    "available_from",
+    "available_until",
+    "regions",
+];
+
+/// fields of Content to return, all of them without a mask or with an empty one
+#[derive(Debug, Default)]
+pub(super) struct ContentMask {
+    fields: Option<HashSet<String>>,
+}
+
+impl ContentMask {
+    pub(super) fn new(mask: Option<&FieldMask>) -> Result<Self> {
+        let paths = match mask {
+            Some(mask) if !mask.paths.is_empty() => &mask.paths,
+            _ => return Ok(Self::default()),
+        };
+        for path in paths {
+            if !CONTENT_FIELDS.contains(&path.as_str()) {
+                bail!("Unknown content field: {}", path);
+            }
+        }
+        Ok(Self {
+            fields: Some(paths.iter().cloned().collect()),
+        })
+    }
+
+    pub(super) fn contains(&self, field: &str) -> bool {
+        self.fields.as_ref().is_none_or(|f| f.contains(field))
+    }
+
+    /// clear the fields not in the mask
+    pub(super) fn apply(&self, content: Content) -> Content {
+        if self.fields.is_none() {
+            return content;
+        }
+        Content {
+            id: content.id,
+            name: self.pick("name", content.name),
+            description: self.pick("description", content.description),
+            publishers: self.pick("publishers", content.publishers),
+            url: self.pick("url", content.url),
+            image: self.pick("image", content.image),
+            r#type: self.pick("type", content.r#type),
+            created_at: self.pick("created_at", content.created_at),
+            views: self.pick("views", content.views),
+            likes: self.pick("likes", content.likes),
+            dislikes: self.pick("dislikes", content.dislikes),
+            translations: self.pick("translations", content.translations),
+            available_from: self.pick("available_from", content.available_from),
+            available_until: self.pick("available_until", content.available_until),
+            regions: self.pick("regions", content.regions),
+        }
+    }
+
+    fn pick<T: Default>(&self, field: &str, value: T) -> T {
+        if self.contains(field) {
+            value
+        } else {
+            T::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::Publisher;
+
+    #[test]
+    fn content_mask_should_work() -> Result<()> {
+        let content = Content {
+            id: 1,
+            name: "Night Train".to_string(),
+            description: "A long night.".to_string(),
+            publishers: vec![Publisher::default()],
+            views: 42,
+            ..Default::default()
+        };
+
+        let mask = ContentMask::new(None)?;
+        assert!(mask.contains("publishers"));
+        assert_eq!(mask.apply(content.clone()), content);
+
+        let mask = ContentMask::new(Some(&FieldMask {
+            paths: vec!["name".to_string(), "views".to_string()],
+        }))?;
+        assert!(!mask.contains("publishers"));
+        let masked = mask.apply(content);
+        assert_eq!(masked.id, 1);
+        assert_eq!(masked.name, "Night Train");
+        assert_eq!(masked.views, 42);
+        assert!(masked.description.is_empty());
+        assert!(masked.publishers.is_empty());
+
+        let mask = FieldMask {
+            paths: vec!["publishers.name".to_string()],
+        };
+        assert!(ContentMask::new(Some(&mask)).is_err());
+        Ok(())
+    }
+}
@@ -1,20 +1,37 @@
-use std::collections::HashSet;
+mod batch;
+mod content;
+mod engagement;
+#[cfg(feature = "fake")]
+mod fake;
+mod import;
+mod list;
+mod locale;
+mod mask;
+mod publisher;
+mod similar;
+mod template;
+mod tpl;
+mod trending;
 
+pub(crate) use engagement::EngagementBuffer;
+pub use locale::locale_chain;
+pub use tpl::{Body, Tpl, BUILTIN_CONTENT_FIELDS};
+
+use self::mask::ContentMask;
 use crate::{
-    pb::{Content, MaterializeRequest, Publisher},
+    pb::{
+        materialize_response::Result as MaterializeResult, Content, MaterializeError,
+        MaterializeErrorCode, MaterializeRequest, MaterializeResponse,
+    },
     MetadataService, ResponseStream, ServiceResult,
 };
-use chrono::{DateTime, Days, Utc};
-use fake::{
-    faker::{chrono::en::DateTimeBetween, lorem::en::Sentence, name::en::Name},
-    Fake, Faker,
-};
-use futures::{stream, Stream, StreamExt};
-use prost_types::Timestamp;
-use rand::Rng;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use sqlx::PgPool;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
+use tracing::warn;
 
 const CHANNEL_SIZE: usize = 1024;
 
@@ -24,10 +41,18 @@ impl MetadataService {
         mut stream: impl Stream<Item = Result<MaterializeRequest, Status>> + Send + 'static + Unpin,
     ) -> ServiceResult<ResponseStream> {
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let pool = self.pool.clone();
         tokio::spawn(async move {
             while let Some(Ok(req)) = stream.next().await {
-                let content = Content::materialize(req.id);
-                tx.send(Ok(content)).await.unwrap();
+                // a bad request fails on its own, the stream goes on
+                let res = match ContentMask::new(req.fields.as_ref()) {
+                    Ok(mask) => materialize_one(&pool, &req, &mask).await,
+                    Err(e) => MaterializeResponse::invalid_argument(req.id, e.to_string()),
+                };
+                // the client is gone
+                if tx.send(Ok(res)).await.is_err() {
+                    break;
+                }
             }
         });
 
@@ -36,78 +61,111 @@ impl MetadataService {
     }
 }
 
-impl Content {
-    pub fn materialize(id: u32) -> Self {
-        let mut rng = rand::thread_rng();
-        Content {
-            id,
-            name: Name().fake(),
-            description: Sentence(3..7).fake(),
-            publishers: (1..rng.gen_range(2..10))
-                .map(|_| Publisher::new())
-                .collect(),
-            url: "https://placehold.co/1600x900".to_string(),
-            image: "https://placehold.co/1600x900".to_string(),
-            r#type: Faker.fake(),
-            created_at: created_at(),
-            views: rng.gen_range(123432..10000000),
-            likes: rng.gen_range(1234..100000),
-            dislikes: rng.gen_range(123..10000),
+async fn materialize_one(
+    pool: &PgPool,
+    req: &MaterializeRequest,
+    mask: &ContentMask,
+) -> MaterializeResponse {
+    match Content::load_with(pool, req.id, mask.contains("publishers")).await {
+        Ok(Some(content))
+            if req.available_only && !content.is_available(Utc::now(), &req.region) =>
+        {
+            MaterializeResponse::unavailable(req.id)
+        }
+        Ok(Some(content)) => MaterializeResponse::content(mask.apply(content)),
+        // demos run without a catalog
+        #[cfg(feature = "fake")]
+        Ok(None) => MaterializeResponse::content(mask.apply(Content::fake(req.id))),
+        #[cfg(not(feature = "fake"))]
+        Ok(None) => MaterializeResponse::not_found(req.id),
+        Err(e) => {
+            warn!("Failed to load content {}: {}", req.id, e);
+            MaterializeResponse::internal(req.id, format!("Database error: {}", e))
         }
     }
 }
 
-pub struct Tpl<'a>(pub &'a [Content]);
-
-impl<'a> Tpl<'a> {
-    pub fn to_body(&self) -> String {
-        format!("Tpl: {:?}", self.0)
+/// map database errors to status, unique violations become AlreadyExists
+pub(crate) fn db_error(e: sqlx::Error) -> Status {
+    match e.as_database_error() {
+        Some(db) if db.is_unique_violation() => Status::already_exists(db.message().to_string()),
+        _ => Status::internal(format!("Database error: {}", e)),
     }
 }
 
-impl MaterializeRequest {
-    pub fn new_with_ids(ids: &[u32]) -> impl Stream<Item = Self> {
-        let reqs: HashSet<_> = ids.iter().map(|id| Self { id: *id }).collect();
-        stream::iter(reqs)
+impl MaterializeResponse {
+    pub fn content(content: Content) -> Self {
+        Self {
+            id: content.id,
+            result: Some(MaterializeResult::Content(content)),
+        }
+    }
+
+    pub fn not_found(id: u32) -> Self {
+        Self::error(id, MaterializeErrorCode::NotFound, "Content not found")
+    }
+
+    pub fn unavailable(id: u32) -> Self {
+        Self::error(
+            id,
+            MaterializeErrorCode::Unavailable,
+            "Content not available",
+        )
+    }
+
+    pub fn invalid_argument(id: u32, message: impl Into<String>) -> Self {
+        Self::error(id, MaterializeErrorCode::InvalidArgument, message)
+    }
+
+    pub fn internal(id: u32, message: impl Into<String>) -> Self {
+        Self::error(id, MaterializeErrorCode::Internal, message)
     }
-}
 
-impl Publisher {
-    pub fn new() -> Self {
-        Publisher {
-            id: (10000..2000000).fake(),
-            name: Name().fake(),
-            avatar: "https://placehold.co/400x400".to_string(),
+    /// the content, or the reason it could not be materialized
+    pub fn into_result(self) -> Result<Content, MaterializeError> {
+        match self.result {
+            Some(MaterializeResult::Content(content)) => Ok(content),
+            Some(MaterializeResult::Error(e)) => Err(e),
+            None => Err(MaterializeError {
+                code: MaterializeErrorCode::Unspecified as i32,
+                message: "Empty materialize result".to_string(),
+            }),
         }
     }
-}
 
-fn before(days: u64) -> DateTime<Utc> {
-    Utc::now().checked_sub_days(Days::new(days)).unwrap()
+    fn error(id: u32, code: MaterializeErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            id,
+            result: Some(MaterializeResult::Error(MaterializeError {
+                code: code as i32,
+                message: message.into(),
+            })),
+        }
+    }
 }
 
-fn created_at() -> Option<Timestamp> {
-    let date: DateTime<Utc> = DateTimeBetween(before(365), before(0)).fake();
-    Some(Timestamp {
-        seconds: date.timestamp(),
-        nanos: date.timestamp_subsec_nanos() as i32,
-    })
+impl MaterializeRequest {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::AppConfig;
     use anyhow::Result;
+    use prost_types::FieldMask;
 
     #[tokio::test]
     async fn materialize_should_work() -> Result<()> {
-        let config = AppConfig::load()?;
-        let service = MetadataService::new(config);
+        let (_tdb, service) = MetadataService::new_for_test().await?;
         let stream = tokio_stream::iter(vec![
-            Ok(MaterializeRequest { id: 1 }),
-            Ok(MaterializeRequest { id: 2 }),
-            Ok(MaterializeRequest { id: 3 }),
+            Ok(MaterializeRequest::new(1)),
+            Ok(MaterializeRequest::new(2)),
+            Ok(MaterializeRequest::new(3)),
         ]);
 
         let response = service.materialize(stream).await?;
@@ -116,4 +174,146 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(not(feature = "fake"))]
+    #[tokio::test]
+    async fn materialize_should_report_missing_content_per_item() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let stream = tokio_stream::iter(vec![
+            Ok(MaterializeRequest::new(404)),
+            Ok(MaterializeRequest::new(2)),
+        ]);
+
+        let response = service.materialize(stream).await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+        assert_eq!(ret.len(), 2);
+
+        let missing = ret.remove(0)?;
+        assert_eq!(missing.id, 404);
+        let err = missing.into_result().unwrap_err();
+        assert_eq!(err.code(), MaterializeErrorCode::NotFound);
+
+        let content = ret.remove(0)?.into_result().unwrap();
+        assert_eq!(content.name, "The Last Orbit");
+        Ok(())
+    }
+
+    #[cfg(feature = "fake")]
+    #[tokio::test]
+    async fn materialize_should_fake_missing_content() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let stream = tokio_stream::iter(vec![Ok(MaterializeRequest::new(404))]);
+        let response = service.materialize(stream).await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+
+        let content = ret.remove(0)?.into_result().unwrap();
+        assert_eq!(content.id, 404);
+        assert!(!content.name.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn materialize_should_return_the_same_content_for_the_same_id() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut contents = Vec::new();
+        for _ in 0..2 {
+            let stream = tokio_stream::iter(vec![Ok(MaterializeRequest::new(2))]);
+            let response = service.materialize(stream).await?;
+            let mut ret = response.into_inner().collect::<Vec<_>>().await;
+            contents.push(ret.remove(0)?.into_result().unwrap());
+        }
+
+        assert_eq!(contents[0], contents[1]);
+        assert_eq!(contents[0].name, "The Last Orbit");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn materialize_should_report_unavailable_content() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        sqlx::query("UPDATE contents SET available_until = '2024-07-01' WHERE id = 3")
+            .execute(&service.pool)
+            .await?;
+        sqlx::query("UPDATE contents SET regions = '{US}' WHERE id = 2")
+            .execute(&service.pool)
+            .await?;
+
+        let reqs = [1, 2, 3].map(|id| MaterializeRequest {
+            id,
+            available_only: true,
+            region: "CN".to_string(),
+            ..Default::default()
+        });
+        let stream = tokio_stream::iter(reqs).map(Ok);
+        let response = service.materialize(stream).await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+        ret.sort_by_key(|r| r.as_ref().map(|r| r.id).unwrap_or_default());
+        let codes: Vec<_> = ret
+            .into_iter()
+            .map(|r| r.unwrap().into_result().map(|c| c.id).map_err(|e| e.code()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                Ok(1),
+                Err(MaterializeErrorCode::Unavailable),
+                Err(MaterializeErrorCode::Unavailable)
+            ]
+        );
+
+        // everything is materialized unless asked otherwise
+        let stream = tokio_stream::iter([2, 3].map(MaterializeRequest::new)).map(Ok);
+        let response = service.materialize(stream).await?;
+        let ret = response.into_inner().collect::<Vec<_>>().await;
+        assert!(ret.into_iter().all(|r| r.unwrap().into_result().is_ok()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn materialize_should_apply_field_mask() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+
+        // only the fields in the mask
+        let req = MaterializeRequest {
+            id: 5,
+            fields: Some(FieldMask {
+                paths: vec!["name".to_string(), "url".to_string()],
+            }),
+            ..Default::default()
+        };
+        let response = service
+            .materialize(tokio_stream::iter(vec![Ok(req)]))
+            .await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+        let content = ret.remove(0)?.into_result().unwrap();
+        assert_eq!(content.name, "Harbor Lights");
+        assert!(content.publishers.is_empty() && content.description.is_empty());
+
+        // an unknown field fails the request but not the ones after it
+        let reqs = vec![
+            Ok(MaterializeRequest {
+                id: 5,
+                fields: Some(FieldMask {
+                    paths: vec!["title".to_string()],
+                }),
+                ..Default::default()
+            }),
+            Ok(MaterializeRequest::new(2)),
+        ];
+        let response = service.materialize(tokio_stream::iter(reqs)).await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+        assert_eq!(ret.len(), 2);
+        let err = ret.remove(0)?.into_result().unwrap_err();
+        assert_eq!(err.code(), MaterializeErrorCode::InvalidArgument);
+        assert!(ret.remove(0)?.into_result().is_ok());
+
+        // all the fields without a mask
+        let response = service
+            .materialize(tokio_stream::iter(vec![Ok(MaterializeRequest::new(5))]))
+            .await?;
+        let mut ret = response.into_inner().collect::<Vec<_>>().await;
+        let content = ret.remove(0)?.into_result().unwrap();
+        assert!(!content.publishers.is_empty() && !content.description.is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,225 @@
+use anyhow::{bail, Result};
+use sqlx::{FromRow, PgPool};
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{
+        CreatePublisherRequest, DeletePublisherRequest, GetPublisherRequest, Publisher,
+        UpdatePublisherRequest,
+    },
+    MetadataService, ServiceResult,
+};
+
+use super::db_error;
+
+const MAX_NAME_LEN: usize = 128;
+const MAX_URL_LEN: usize = 512;
+
+#[derive(FromRow, Debug, Clone)]
+pub(super) struct PublisherModel {
+    pub(super) id: i32,
+    name: String,
+    avatar: String,
+}
+
+impl MetadataService {
+    pub async fn create_publisher(&self, req: CreatePublisherRequest) -> ServiceResult<Publisher> {
+        let publisher = req
+            .publisher
+            .ok_or_else(|| Status::invalid_argument("Publisher is required"))?;
+        validate_publisher(&publisher).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        sqlx::query("INSERT INTO publishers (id, name, avatar) VALUES ($1, $2, $3)")
+            .bind(publisher.id as i32)
+            .bind(&publisher.name)
+            .bind(&publisher.avatar)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        self.get_publisher(GetPublisherRequest { id: publisher.id })
+            .await
+    }
+
+    pub async fn update_publisher(&self, req: UpdatePublisherRequest) -> ServiceResult<Publisher> {
+        let publisher = req
+            .publisher
+            .ok_or_else(|| Status::invalid_argument("Publisher is required"))?;
+        validate_publisher(&publisher).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let ret = sqlx::query("UPDATE publishers SET name = $2, avatar = $3 WHERE id = $1")
+            .bind(publisher.id as i32)
+            .bind(&publisher.name)
+            .bind(&publisher.avatar)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        if ret.rows_affected() == 0 {
+            return Err(not_found(publisher.id));
+        }
+
+        self.get_publisher(GetPublisherRequest { id: publisher.id })
+            .await
+    }
+
+    /// insert the publisher, or update it if the id exists
+    pub(super) async fn upsert_publisher(&self, publisher: &Publisher) -> Result<(), Status> {
+        validate_publisher(publisher).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        sqlx::query(
+            r#"INSERT INTO publishers (id, name, avatar) VALUES ($1, $2, $3)
+            ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, avatar = EXCLUDED.avatar"#,
+        )
+        .bind(publisher.id as i32)
+        .bind(&publisher.name)
+        .bind(&publisher.avatar)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    pub async fn get_publisher(&self, req: GetPublisherRequest) -> ServiceResult<Publisher> {
+        match Publisher::load(&self.pool, req.id)
+            .await
+            .map_err(db_error)?
+        {
+            Some(publisher) => Ok(Response::new(publisher)),
+            None => Err(not_found(req.id)),
+        }
+    }
+
+    pub async fn delete_publisher(&self, req: DeletePublisherRequest) -> ServiceResult<Publisher> {
+        let publisher = self
+            .get_publisher(GetPublisherRequest { id: req.id })
+            .await?
+            .into_inner();
+
+        let (referenced,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM contents WHERE publisher_ids @> ARRAY[$1])",
+        )
+        .bind(req.id as i32)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(db_error)?;
+        if referenced {
+            return Err(Status::failed_precondition(format!(
+                "Publisher {} is still referenced by contents",
+                req.id
+            )));
+        }
+
+        sqlx::query("DELETE FROM publishers WHERE id = $1")
+            .bind(req.id as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(Response::new(publisher))
+    }
+}
+
+impl Publisher {
+    pub async fn load(pool: &PgPool, id: u32) -> sqlx::Result<Option<Self>> {
+        // no publisher has an id out of the range of the column
+        let Ok(id) = i32::try_from(id) else {
+            return Ok(None);
+        };
+        let publisher = sqlx::query_as::<_, PublisherModel>(
+            "SELECT id, name, avatar FROM publishers WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+        Ok(publisher.map(|p| p.into_publisher()))
+    }
+}
+
+impl PublisherModel {
+    pub(super) fn into_publisher(self) -> Publisher {
+        Publisher {
+            id: self.id as u32,
+            name: self.name,
+            avatar: self.avatar,
+        }
+    }
+}
+
+fn validate_publisher(publisher: &Publisher) -> Result<()> {
+    if publisher.id == 0 || i32::try_from(publisher.id).is_err() {
+        bail!("Publisher id must be positive and at most {}", i32::MAX);
+    }
+    if publisher.name.trim().is_empty() {
+        bail!("Publisher name is required");
+    }
+    if publisher.name.chars().count() > MAX_NAME_LEN {
+        bail!("Publisher name is longer than {} characters", MAX_NAME_LEN);
+    }
+    if publisher.avatar.len() > MAX_URL_LEN {
+        bail!("Publisher avatar must be within {} bytes", MAX_URL_LEN);
+    }
+    Ok(())
+}
+
+fn not_found(id: u32) -> Status {
+    Status::not_found(format!("Publisher not found: {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn publisher_crud_should_work() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let mut publisher = Publisher {
+            id: 20001,
+            name: "Mae Runolfsson".to_string(),
+            avatar: "https://placehold.co/400x400".to_string(),
+        };
+        let req = CreatePublisherRequest {
+            publisher: Some(publisher.clone()),
+        };
+        let created = service.create_publisher(req).await?.into_inner();
+        assert_eq!(created, publisher);
+
+        publisher.name = "Mae R.".to_string();
+        let req = UpdatePublisherRequest {
+            publisher: Some(publisher.clone()),
+        };
+        let updated = service.update_publisher(req).await?.into_inner();
+        assert_eq!(updated.name, "Mae R.");
+
+        let deleted = service
+            .delete_publisher(DeletePublisherRequest { id: 20001 })
+            .await?
+            .into_inner();
+        assert_eq!(deleted, publisher);
+
+        let err = service
+            .get_publisher(GetPublisherRequest { id: 20001 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn referenced_publisher_should_not_be_deleted() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let err = service
+            .delete_publisher(DeletePublisherRequest { id: 10001 })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let req = CreatePublisherRequest {
+            publisher: Some(Publisher {
+                id: 20002,
+                ..Default::default()
+            }),
+        };
+        let err = service.create_publisher(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+}
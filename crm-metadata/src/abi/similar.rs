@@ -0,0 +1,139 @@
+use sqlx::FromRow;
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{ScoredContent, SimilarRequest, SimilarResponse},
+    MetadataService, ServiceResult,
+};
+
+use super::{
+    content::{ContentModel, CONTENT_COLUMNS},
+    db_error,
+};
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+/// score of every publisher shared with the given contents
+const PUBLISHER_WEIGHT: f64 = 2.0;
+/// score of having the same type as any of the given contents
+const TYPE_WEIGHT: f64 = 1.0;
+/// score of the co-watch count, on a log scale
+const CO_WATCH_WEIGHT: f64 = 1.0;
+
+#[derive(FromRow)]
+struct SimilarModel {
+    #[sqlx(flatten)]
+    content: ContentModel,
+    score: f64,
+}
+
+impl MetadataService {
+    pub async fn similar(&self, req: SimilarRequest) -> ServiceResult<SimilarResponse> {
+        if req.content_ids.is_empty() {
+            return Err(Status::invalid_argument("Content ids are required"));
+        }
+        let limit = match req.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+        let ids: Vec<i32> = req
+            .content_ids
+            .iter()
+            .filter_map(|id| i32::try_from(*id).ok())
+            .collect();
+
+        let sql = format!(
+            r#"WITH src AS (SELECT type, publisher_ids FROM contents WHERE id = ANY($1)),
+            co AS (
+                SELECT other_id, sum(count)::float8 AS count FROM co_watches
+                WHERE content_id = ANY($1) GROUP BY other_id
+            ),
+            scored AS (
+                SELECT c.id,
+                    $2 * (SELECT count(DISTINCT p) FROM src, unnest(src.publisher_ids) p WHERE p = ANY(c.publisher_ids))
+                    + CASE WHEN c.type IN (SELECT type FROM src) THEN $3 ELSE 0 END
+                    + $4 * ln(1 + coalesce(co.count, 0)) AS score
+                FROM contents c LEFT JOIN co ON co.other_id = c.id
+                WHERE c.id <> ALL($1)
+            )
+            SELECT {}, score FROM contents JOIN scored USING (id)
+            WHERE score > 0 ORDER BY score DESC, id LIMIT $5"#,
+            CONTENT_COLUMNS
+        );
+        let rows = sqlx::query_as::<_, SimilarModel>(&sql)
+            .bind(&ids)
+            .bind(PUBLISHER_WEIGHT)
+            .bind(TYPE_WEIGHT)
+            .bind(CO_WATCH_WEIGHT)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let (rows, scores): (Vec<_>, Vec<_>) =
+            rows.into_iter().map(|r| (r.content, r.score)).unzip();
+        let contents = ContentModel::into_contents(&self.pool, rows)
+            .await
+            .map_err(db_error)?;
+        let contents = contents
+            .into_iter()
+            .zip(scores)
+            .map(|(content, score)| ScoredContent {
+                content: Some(content),
+                score,
+            })
+            .collect();
+        Ok(Response::new(SimilarResponse { contents }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::{EngagementEvent, EngagementKind};
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn similar_should_rank_related_contents() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        // 3 and 4 have nothing in common with 2, but are watched together with it
+        let mut events = Vec::new();
+        for (other, viewers) in [(3, 10), (4, 1)] {
+            for i in 0..viewers {
+                for id in [2, other] {
+                    events.push(EngagementEvent {
+                        content_id: id,
+                        kind: EngagementKind::View as i32,
+                        viewer: format!("{}-{}", other, i),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        let stream = tokio_stream::iter(events).map(Ok);
+        service.record_engagement(stream).await?;
+        service.flush_engagement().await?;
+
+        let req = SimilarRequest {
+            content_ids: vec![2],
+            limit: 10,
+        };
+        let ret = service.similar(req).await?.into_inner();
+        let ids: Vec<_> = ret
+            .contents
+            .iter()
+            .map(|c| c.content.as_ref().unwrap().id)
+            .collect();
+        // 5 shares the publisher and the type, 1 has nothing to do with 2
+        assert_eq!(ids, [5, 3, 4]);
+        assert!(ret.contents.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let err = service
+            .similar(SimilarRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+}
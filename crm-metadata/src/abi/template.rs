@@ -0,0 +1,401 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{
+        Content, CreateTemplateRequest, GetActiveTemplateRequest, PublishTemplateRequest,
+        RenderRequest, RenderResponse, Template, TemplateChannel,
+    },
+    MetadataService, ServiceResult,
+};
+
+use super::{content::to_ts, db_error, locale::locale_chain, Tpl};
+
+/// locale of templates created or requested without one
+pub(super) const DEFAULT_LOCALE: &str = "en";
+const MAX_NAME_LEN: usize = 128;
+const MAX_LOCALE_LEN: usize = 16;
+const TEMPLATE_COLUMNS: &str = "name, locale, version, channel::text AS channel, subject, text_body, html_body, active, created_at, published_at";
+
+#[derive(FromRow, Debug)]
+struct TemplateModel {
+    name: String,
+    locale: String,
+    version: i32,
+    channel: String,
+    subject: String,
+    text_body: String,
+    html_body: String,
+    active: bool,
+    created_at: DateTime<Utc>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl MetadataService {
+    pub async fn create_template(&self, req: CreateTemplateRequest) -> ServiceResult<Template> {
+        let mut template = req
+            .template
+            .ok_or_else(|| Status::invalid_argument("Template is required"))?;
+        if template.locale.is_empty() {
+            template.locale = DEFAULT_LOCALE.to_string();
+        }
+        validate_template(&template).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        // concurrent creations of the same name and locale conflict on the primary key
+        let sql = format!(
+            r#"INSERT INTO templates (name, locale, version, channel, subject, text_body, html_body)
+            SELECT $1, $2, coalesce(max(version), 0) + 1, $3::template_channel, $4, $5, $6
+            FROM templates WHERE name = $1 AND locale = $2
+            RETURNING {}"#,
+            TEMPLATE_COLUMNS
+        );
+        let template = sqlx::query_as::<_, TemplateModel>(&sql)
+            .bind(&template.name)
+            .bind(&template.locale)
+            .bind(channel_name(template.channel()))
+            .bind(&template.subject)
+            .bind(&template.text_body)
+            .bind(&template.html_body)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(Response::new(template.into_template()))
+    }
+
+    pub async fn publish_template(&self, req: PublishTemplateRequest) -> ServiceResult<Template> {
+        let locale = match req.locale.as_str() {
+            "" => DEFAULT_LOCALE,
+            locale => locale,
+        };
+
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlx::query(
+            "UPDATE templates SET active = false WHERE name = $1 AND locale = $2 AND active",
+        )
+        .bind(&req.name)
+        .bind(locale)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        let sql = format!(
+            r#"UPDATE templates SET active = true, published_at = coalesce(published_at, now())
+            WHERE name = $1 AND locale = $2 AND version = $3
+            RETURNING {}"#,
+            TEMPLATE_COLUMNS
+        );
+        let template = sqlx::query_as::<_, TemplateModel>(&sql)
+            .bind(&req.name)
+            .bind(locale)
+            .bind(req.version as i32)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        // dropping the transaction rolls back the deactivation
+        let Some(template) = template else {
+            return Err(Status::not_found(format!(
+                "Template not found: {}@{} v{}",
+                req.name, locale, req.version
+            )));
+        };
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(Response::new(template.into_template()))
+    }
+
+    pub async fn get_active_template(
+        &self,
+        req: GetActiveTemplateRequest,
+    ) -> ServiceResult<Template> {
+        let template = self.load_template(&req.name, &req.locale, None).await?;
+        Ok(Response::new(template))
+    }
+
+    pub async fn render(&self, req: RenderRequest) -> ServiceResult<RenderResponse> {
+        let version = match req.version {
+            0 => None,
+            v => Some(v),
+        };
+        let template = self.load_template(&req.name, &req.locale, version).await?;
+
+        let mut contents = Vec::with_capacity(req.content_ids.len());
+        for id in req.content_ids {
+            match Content::load(&self.pool, id).await.map_err(db_error)? {
+                Some(content) => contents.push(content),
+                None => return Err(Status::not_found(format!("Content not found: {}", id))),
+            }
+        }
+
+        let mut user = req.user;
+        if !req.locale.is_empty() {
+            user.entry("locale".to_string()).or_insert(req.locale);
+        }
+        let body = Tpl::new(&template)
+            .and_then(|tpl| tpl.render(&contents, &user))
+            .map_err(|e| Status::failed_precondition(format!("Failed to render: {}", e)))?;
+        Ok(Response::new(RenderResponse {
+            subject: body.subject,
+            text: body.text,
+            html: body.html,
+        }))
+    }
+
+    /// the given version of the template, or the published one of the first locale in the
+    /// fallback chain without a version
+    async fn load_template(
+        &self,
+        name: &str,
+        locale: &str,
+        version: Option<u32>,
+    ) -> Result<Template, Status> {
+        let locale = match locale {
+            "" => DEFAULT_LOCALE,
+            locale => locale,
+        };
+
+        let template = match version {
+            Some(version) => {
+                let sql = format!(
+                    "SELECT {} FROM templates WHERE name = $1 AND locale = $2 AND version = $3",
+                    TEMPLATE_COLUMNS
+                );
+                sqlx::query_as::<_, TemplateModel>(&sql)
+                    .bind(name)
+                    .bind(locale)
+                    .bind(version as i32)
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+            None => {
+                let sql = format!(
+                    r#"SELECT {} FROM templates
+                    WHERE name = $1 AND active AND lower(replace(locale, '_', '-')) = ANY($2)
+                    ORDER BY array_position($2, lower(replace(locale, '_', '-'))) LIMIT 1"#,
+                    TEMPLATE_COLUMNS
+                );
+                sqlx::query_as::<_, TemplateModel>(&sql)
+                    .bind(name)
+                    .bind(locale_chain(locale))
+                    .fetch_optional(&self.pool)
+                    .await
+            }
+        }
+        .map_err(db_error)?;
+        match (template, version) {
+            (Some(template), _) => Ok(template.into_template()),
+            (None, Some(version)) => Err(Status::not_found(format!(
+                "Template not found: {}@{} v{}",
+                name, locale, version
+            ))),
+            (None, None) => Err(Status::not_found(format!(
+                "No published template: {}@{}",
+                name, locale
+            ))),
+        }
+    }
+}
+
+impl TemplateModel {
+    fn into_template(self) -> Template {
+        let channel = format!("TEMPLATE_CHANNEL_{}", self.channel.to_uppercase());
+        Template {
+            name: self.name,
+            version: self.version as u32,
+            channel: TemplateChannel::from_str_name(&channel).unwrap_or_default() as i32,
+            locale: self.locale,
+            subject: self.subject,
+            text_body: self.text_body,
+            html_body: self.html_body,
+            active: self.active,
+            created_at: Some(to_ts(self.created_at)),
+            published_at: self.published_at.map(to_ts),
+        }
+    }
+}
+
+fn validate_template(template: &Template) -> Result<()> {
+    if template.name.trim().is_empty() {
+        bail!("Template name is required");
+    }
+    if template.name.chars().count() > MAX_NAME_LEN {
+        bail!("Template name is longer than {} characters", MAX_NAME_LEN);
+    }
+    if template.locale.len() > MAX_LOCALE_LEN {
+        bail!("Template locale is longer than {} bytes", MAX_LOCALE_LEN);
+    }
+    match TemplateChannel::try_from(template.channel) {
+        Ok(TemplateChannel::Unspecified) | Err(_) => {
+            bail!("Invalid template channel: {}", template.channel)
+        }
+        Ok(_) => {}
+    }
+    if template.text_body.trim().is_empty() {
+        bail!("Template text body is required");
+    }
+    // catch syntax errors before the template is used by a campaign
+    Tpl::new(template)?;
+    Ok(())
+}
+
+/// TemplateChannel to template_channel label in postgres, e.g. TEMPLATE_CHANNEL_EMAIL is email
+fn channel_name(channel: TemplateChannel) -> String {
+    channel
+        .as_str_name()
+        .trim_start_matches("TEMPLATE_CHANNEL_")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn template_versions_should_work() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let err = get_active(&service, "welcome").await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        for subject in ["Welcome", "Welcome, {{ user.name }}"] {
+            let req = CreateTemplateRequest {
+                template: Some(Template::builtin("welcome", subject)),
+            };
+            service.create_template(req).await?;
+        }
+        // nothing is published yet
+        assert!(get_active(&service, "welcome").await.is_err());
+
+        for version in [2, 1] {
+            let req = PublishTemplateRequest {
+                name: "welcome".to_string(),
+                locale: String::new(),
+                version,
+            };
+            let template = service.publish_template(req).await?.into_inner();
+            assert!(template.active);
+            assert_eq!(template.version, version);
+
+            let template = get_active(&service, "welcome").await?;
+            assert_eq!(template.version, version);
+            assert_eq!(template.locale, "en");
+            assert_eq!(template.channel(), TemplateChannel::Email);
+        }
+
+        let req = PublishTemplateRequest {
+            name: "welcome".to_string(),
+            locale: String::new(),
+            version: 3,
+        };
+        let err = service.publish_template(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        // a failed publish keeps the active version
+        assert_eq!(get_active(&service, "welcome").await?.version, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn template_locales_should_fall_back() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        for (locale, subject) in [("en", "Welcome"), ("zh", "欢迎")] {
+            let req = CreateTemplateRequest {
+                template: Some(Template {
+                    locale: locale.to_string(),
+                    ..Template::builtin("welcome", subject)
+                }),
+            };
+            service.create_template(req).await?;
+            let req = PublishTemplateRequest {
+                name: "welcome".to_string(),
+                locale: locale.to_string(),
+                version: 1,
+            };
+            service.publish_template(req).await?;
+        }
+
+        for (locale, expected) in [
+            ("zh-CN", "zh"),
+            ("zh_TW", "zh"),
+            ("fr-FR", "en"),
+            ("", "en"),
+        ] {
+            let req = GetActiveTemplateRequest {
+                name: "welcome".to_string(),
+                locale: locale.to_string(),
+            };
+            let template = service.get_active_template(req).await?.into_inner();
+            assert_eq!(template.locale, expected);
+        }
+
+        // an explicit version does not fall back
+        let req = RenderRequest {
+            name: "welcome".to_string(),
+            locale: "zh-CN".to_string(),
+            version: 1,
+            ..Default::default()
+        };
+        let err = service.render(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_template_should_be_rejected() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let templates = [
+            Template {
+                channel: TemplateChannel::Unspecified as i32,
+                ..Template::builtin("welcome", "Welcome")
+            },
+            Template::builtin("", "Welcome"),
+            Template::builtin("welcome", "{% if %}"),
+        ];
+        for template in templates {
+            let req = CreateTemplateRequest {
+                template: Some(template),
+            };
+            let err = service.create_template(req).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn render_should_work() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let req = CreateTemplateRequest {
+            template: Some(Template::builtin("welcome", "Hi {{ user.name }}")),
+        };
+        service.create_template(req).await?;
+
+        let mut req = RenderRequest {
+            name: "welcome".to_string(),
+            version: 1,
+            user: [("name".to_string(), "Mae".to_string())].into(),
+            content_ids: vec![2],
+            ..Default::default()
+        };
+        let ret = service.render(req.clone()).await?.into_inner();
+        assert_eq!(ret.subject, "Hi Mae");
+        assert!(ret.text.contains("1. The Last Orbit by Dewitt Kunde"));
+        assert!(ret.html.contains("<p>Hi Mae,</p>"));
+
+        // not published yet
+        req.version = 0;
+        let err = service.render(req.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        req.version = 1;
+        req.content_ids = vec![404];
+        let err = service.render(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    async fn get_active(service: &MetadataService, name: &str) -> Result<Template, Status> {
+        let req = GetActiveTemplateRequest {
+            name: name.to_string(),
+            locale: String::new(),
+        };
+        Ok(service.get_active_template(req).await?.into_inner())
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{TimeZone, Utc};
+use minijinja::{context, Environment};
+use serde::Serialize;
+
+use crate::pb::{Content, Publisher, Template, TemplateChannel};
+
+use super::{content::type_name, locale::locale_chain, template::DEFAULT_LOCALE};
+
+const SUBJECT: &str = "subject";
+const TEXT: &str = "text";
+/// templates ending with .html are auto-escaped
+const HTML: &str = "body.html";
+
+/// content fields the built-in templates render, translations localize name and description
+pub const BUILTIN_CONTENT_FIELDS: &[&str] = &[
+    "name",
+    "description",
+    "publishers",
+    "url",
+    "image",
+    "translations",
+];
+
+/// compiled template, compile once and render for every user
+pub struct Tpl {
+    env: Environment<'static>,
+    has_html: bool,
+}
+
+/// rendered notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Body {
+    pub subject: String,
+    pub text: String,
+    /// empty if the template has no html body
+    pub html: String,
+}
+
+/// what the templates see of a content
+#[derive(Serialize)]
+struct ContentView<'a> {
+    id: u32,
+    name: &'a str,
+    description: &'a str,
+    publishers: Vec<PublisherView<'a>>,
+    url: &'a str,
+    image: &'a str,
+    #[serde(rename = "type")]
+    content_type: String,
+    created_at: Option<String>,
+    views: u64,
+    likes: u64,
+    dislikes: u64,
+}
+
+#[derive(Serialize)]
+struct PublisherView<'a> {
+    id: u32,
+    name: &'a str,
+    avatar: &'a str,
+}
+
+impl Tpl {
+    pub fn new(template: &Template) -> Result<Self> {
+        let mut env = Environment::new();
+        env.add_template_owned(SUBJECT, template.subject.clone())?;
+        env.add_template_owned(TEXT, template.text_body.clone())?;
+        let has_html = !template.html_body.is_empty();
+        if has_html {
+            env.add_template_owned(HTML, template.html_body.clone())?;
+        }
+        Ok(Self { env, has_html })
+    }
+
+    /// render the contents for a user, user variables like name are available as `user.name`.
+    /// Contents are localized by `user.locale`.
+    pub fn render(&self, contents: &[Content], user: &HashMap<String, String>) -> Result<Body> {
+        let chain = locale_chain(user.get("locale").map_or("", |l| l.as_str()));
+        let contents: Vec<_> = contents
+            .iter()
+            .map(|c| ContentView::new(c, &chain))
+            .collect();
+        let ctx = context! { contents, user };
+
+        let html = if self.has_html {
+            self.env.get_template(HTML)?.render(&ctx)?
+        } else {
+            String::new()
+        };
+        Ok(Body {
+            // a subject is always a single line
+            subject: self
+                .env
+                .get_template(SUBJECT)?
+                .render(&ctx)?
+                .trim()
+                .to_string(),
+            text: self.env.get_template(TEXT)?.render(&ctx)?,
+            html,
+        })
+    }
+}
+
+impl Template {
+    /// built-in email template, used when there is no published version of a template
+    pub fn builtin(name: impl Into<String>, subject: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            channel: TemplateChannel::Email as i32,
+            locale: DEFAULT_LOCALE.to_string(),
+            subject: subject.into(),
+            text_body: include_str!("../../templates/email.txt").to_string(),
+            html_body: include_str!("../../templates/email.html").to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a> ContentView<'a> {
+    fn new(c: &'a Content, chain: &[String]) -> Self {
+        let (name, description) = c.localized(chain);
+        Self {
+            id: c.id,
+            name,
+            description,
+            publishers: c.publishers.iter().map(PublisherView::from).collect(),
+            url: &c.url,
+            image: &c.image,
+            content_type: type_name(c.r#type()),
+            created_at: c.created_at.as_ref().and_then(|ts| {
+                Utc.timestamp_opt(ts.seconds, ts.nanos as _)
+                    .single()
+                    .map(|dt| dt.to_rfc3339())
+            }),
+            views: c.views,
+            likes: c.likes,
+            dislikes: c.dislikes,
+        }
+    }
+}
+
+impl<'a> From<&'a Publisher> for PublisherView<'a> {
+    fn from(p: &'a Publisher) -> Self {
+        Self {
+            id: p.id,
+            name: &p.name,
+            avatar: &p.avatar,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{abi::mask::ContentMask, pb::ContentTranslation};
+    use prost_types::FieldMask;
+
+    #[test]
+    fn render_should_work() -> Result<()> {
+        let contents = vec![Content {
+            id: 1,
+            name: "Tom & Jerry".to_string(),
+            description: "A cat and a mouse.".to_string(),
+            publishers: vec![
+                Publisher {
+                    id: 1,
+                    name: "Lorna Hills".to_string(),
+                    avatar: "https://placehold.co/400x400".to_string(),
+                },
+                Publisher {
+                    id: 2,
+                    name: "Jarret Mraz".to_string(),
+                    avatar: "https://placehold.co/400x400".to_string(),
+                },
+            ],
+            url: "https://example.com/1".to_string(),
+            image: "https://placehold.co/1600x900".to_string(),
+            ..Default::default()
+        }];
+        let user = HashMap::from([("name".to_string(), "Mae".to_string())]);
+        let tpl = Tpl::new(&Template::builtin(
+            "welcome",
+            "New for you, {{ user.name }}",
+        ))?;
+        let body = tpl.render(&contents, &user)?;
+
+        assert_eq!(body.subject, "New for you, Mae");
+        assert!(body.text.starts_with("Hi Mae,"));
+        assert!(body
+            .text
+            .contains("1. Tom & Jerry by Lorna Hills, Jarret Mraz\n   A cat and a mouse.\n   https://example.com/1\n"));
+
+        assert!(body.html.contains("Tom &amp; Jerry"));
+        assert!(body
+            .html
+            .contains(r#"<img src="https:&#x2f;&#x2f;placehold.co&#x2f;1600x900""#));
+        Ok(())
+    }
+
+    #[test]
+    fn render_should_fall_back_without_user_name() -> Result<()> {
+        let tpl = Tpl::new(&Template::builtin("welcome", "Welcome"))?;
+        let body = tpl.render(&[], &HashMap::new())?;
+        assert!(body.text.starts_with("Hi there,"));
+        assert!(body.html.contains("<p>Hi there,</p>"));
+        Ok(())
+    }
+
+    #[test]
+    fn render_should_localize_contents() -> Result<()> {
+        let contents = vec![Content {
+            id: 2,
+            name: "The Last Orbit".to_string(),
+            description: "A crew fights to return home.".to_string(),
+            translations: [(
+                "zh".to_string(),
+                ContentTranslation {
+                    name: "最后的轨道".to_string(),
+                    description: "一群宇航员努力返回家园。".to_string(),
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }];
+        let tpl = Tpl::new(&Template::builtin("welcome", "{{ contents[0].name }}"))?;
+
+        let user = HashMap::from([("locale".to_string(), "zh-CN".to_string())]);
+        let body = tpl.render(&contents, &user)?;
+        assert_eq!(body.subject, "最后的轨道");
+        assert!(body.text.contains("一群宇航员努力返回家园。"));
+
+        let user = HashMap::from([("locale".to_string(), "fr".to_string())]);
+        assert_eq!(tpl.render(&contents, &user)?.subject, "The Last Orbit");
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_content_fields_should_cover_the_builtin_template() -> Result<()> {
+        let contents = vec![Content {
+            id: 5,
+            name: "Harbor Lights".to_string(),
+            description: "A quiet story of a fishing town.".to_string(),
+            publishers: vec![Publisher {
+                id: 10003,
+                name: "Dewitt Kunde".to_string(),
+                avatar: "https://placehold.co/400x400".to_string(),
+            }],
+            url: "https://example.com/5".to_string(),
+            image: "https://placehold.co/1600x900".to_string(),
+            views: 3345123,
+            translations: [(
+                "zh".to_string(),
+                ContentTranslation {
+                    name: "港湾灯火".to_string(),
+                    description: "一个渔港小镇的安静故事。".to_string(),
+                },
+            )]
+            .into(),
+            ..Default::default()
+        }];
+        let mask = ContentMask::new(Some(&FieldMask {
+            paths: BUILTIN_CONTENT_FIELDS
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+        }))?;
+        let masked: Vec<_> = contents.iter().map(|c| mask.apply(c.clone())).collect();
+        assert_eq!(masked[0].views, 0);
+
+        let tpl = Tpl::new(&Template::builtin("remind", "Remind"))?;
+        for locale in ["en", "zh-CN"] {
+            let user = HashMap::from([("locale".to_string(), locale.to_string())]);
+            let body = tpl.render(&masked, &user)?;
+            assert_eq!(body, tpl.render(&contents, &user)?);
+            assert!(body.text.contains(" by Dewitt Kunde\n"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_template_should_be_rejected() {
+        let template = Template {
+            subject: "Hi {{ user.name".to_string(),
+            ..Template::builtin("welcome", "")
+        };
+        assert!(Tpl::new(&template).is_err());
+    }
+}
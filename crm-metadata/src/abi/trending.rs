@@ -0,0 +1,175 @@
+use sqlx::FromRow;
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{ContentType, ScoredContent, TrendingRequest, TrendingResponse},
+    MetadataService, ServiceResult,
+};
+
+use super::{
+    content::{type_name, ContentModel, CONTENT_COLUMNS},
+    db_error,
+};
+
+/// a week
+const DEFAULT_WINDOW: u32 = 168;
+/// 90 days
+const MAX_WINDOW: u32 = 2160;
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+/// z-score of the 95% confidence level
+const WILSON_Z: f64 = 1.96;
+
+#[derive(FromRow)]
+struct TrendingModel {
+    #[sqlx(flatten)]
+    content: ContentModel,
+    score: f64,
+}
+
+impl MetadataService {
+    /// rank the contents by their engagement within the window: views on a log scale, each
+    /// hour decayed by its age relative to the window, then weighted by how well liked the
+    /// content is. A content without any vote keeps half of its popularity
+    pub async fn trending(&self, req: TrendingRequest) -> ServiceResult<TrendingResponse> {
+        let content_type = ContentType::try_from(req.r#type).map_err(|_| {
+            Status::invalid_argument(format!("Invalid content type: {}", req.r#type))
+        })?;
+        let window = match req.window {
+            0 => DEFAULT_WINDOW,
+            n if n > MAX_WINDOW => {
+                return Err(Status::invalid_argument(format!(
+                    "Window must be at most {} hours",
+                    MAX_WINDOW
+                )))
+            }
+            n => n,
+        };
+        let limit = match req.limit {
+            0 => DEFAULT_LIMIT,
+            n => n.min(MAX_LIMIT),
+        };
+
+        let sql = format!(
+            r#"WITH recent AS (
+                SELECT content_id,
+                    sum(views * exp(-greatest(extract(epoch FROM now() - hour), 0) / 3600 / $1))::float8 AS recent_views,
+                    sum(likes)::float8 AS recent_likes, sum(dislikes)::float8 AS recent_dislikes
+                FROM content_engagement WHERE hour >= now() - make_interval(hours => $1)
+                GROUP BY content_id
+            )
+            SELECT {}, ln(1 + recent_views) * (0.5 + wilson_lower_bound(recent_likes, recent_dislikes, $3)) AS score
+            FROM contents JOIN recent ON recent.content_id = contents.id
+            WHERE $2::text IS NULL OR type = $2::content_type
+            ORDER BY score DESC, id LIMIT $4"#,
+            CONTENT_COLUMNS
+        );
+        let content_type = match content_type {
+            ContentType::Unspecified => None,
+            t => Some(type_name(t)),
+        };
+        let rows = sqlx::query_as::<_, TrendingModel>(&sql)
+            .bind(window as i32)
+            .bind(content_type)
+            .bind(WILSON_Z)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(db_error)?;
+
+        let (rows, scores): (Vec<_>, Vec<_>) =
+            rows.into_iter().map(|r| (r.content, r.score)).unzip();
+        let contents = ContentModel::into_contents(&self.pool, rows)
+            .await
+            .map_err(db_error)?;
+        let contents = contents
+            .into_iter()
+            .zip(scores)
+            .map(|(content, score)| ScoredContent {
+                content: Some(content),
+                score,
+            })
+            .collect();
+        Ok(Response::new(TrendingResponse { contents }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[tokio::test]
+    async fn wilson_lower_bound_should_favor_larger_samples() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        let bound = |likes: f64, dislikes: f64| {
+            sqlx::query_scalar::<_, f64>("SELECT wilson_lower_bound($1, $2, $3)")
+                .bind(likes)
+                .bind(dislikes)
+                .bind(WILSON_Z)
+                .fetch_one(&service.pool)
+        };
+        assert_eq!(bound(0.0, 0.0).await?, 0.0);
+        // same ratio, more votes
+        assert!(bound(900.0, 100.0).await? > bound(9.0, 1.0).await?);
+        assert!(bound(9.0, 1.0).await? < 0.9);
+        assert!(bound(100.0, 0.0).await? <= 1.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn trending_should_rank_by_recent_engagement() -> Result<()> {
+        let (_tdb, service) = MetadataService::new_for_test().await?;
+        // 2 has the most views of all time, but none within the last week
+        for (id, hours_ago, views, likes, dislikes) in [
+            (2, 200, 100000, 1000, 0),
+            (1, 1, 500, 50, 0),
+            (3, 2, 500, 0, 50),
+            (5, 100, 500, 50, 0),
+            (4, 0, 10, 0, 0),
+        ] {
+            sqlx::query(
+                r#"INSERT INTO content_engagement (content_id, hour, views, likes, dislikes)
+                VALUES ($1, date_trunc('hour', now()) - make_interval(hours => $2), $3, $4, $5)"#,
+            )
+            .bind(id)
+            .bind(hours_ago)
+            .bind(views as i64)
+            .bind(likes as i64)
+            .bind(dislikes as i64)
+            .execute(&service.pool)
+            .await?;
+        }
+
+        let ret = service
+            .trending(TrendingRequest::default())
+            .await?
+            .into_inner();
+        // well liked before disliked, recent before older
+        assert_eq!(ids(&ret), [1, 5, 3, 4]);
+        assert!(ret.contents.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let req = TrendingRequest {
+            r#type: ContentType::Movie as i32,
+            window: 24 * 30,
+            limit: 1,
+        };
+        let ret = service.trending(req).await?.into_inner();
+        assert_eq!(ids(&ret), [2]);
+
+        let req = TrendingRequest {
+            window: u32::MAX,
+            ..Default::default()
+        };
+        let err = service.trending(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        Ok(())
+    }
+
+    fn ids(ret: &TrendingResponse) -> Vec<u32> {
+        ret.contents
+            .iter()
+            .map(|c| c.content.as_ref().unwrap().id)
+            .collect()
+    }
+}
@@ -0,0 +1,292 @@
+//! Import publishers and contents exported from the CMS.
+//!
+//! Usage: import <file.ndjson|file.csv> [server url]
+//!
+//! Every row has a `kind` of publisher or content. In NDJSON a row is a json object per line,
+//! in CSV the header names the columns and publisher_ids are separated by `;`.
+use std::{env, fs, path::Path, process};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use crm_metadata::{
+    pb::{
+        import_row::Row, metadata_client::MetadataClient, Content, ContentType, ImportRow,
+        Publisher,
+    },
+    AppConfig,
+};
+use prost_types::Timestamp;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Publisher,
+    Content,
+}
+
+/// a row of either kind, the columns of the other kind are left empty
+#[derive(Debug, Deserialize)]
+struct Record {
+    kind: Kind,
+    id: u32,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    avatar: String,
+    #[serde(default)]
+    publisher_ids: Ids,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    image: String,
+    #[serde(default, rename = "type")]
+    content_type: String,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    views: Option<u64>,
+    #[serde(default)]
+    likes: Option<u64>,
+    #[serde(default)]
+    dislikes: Option<u64>,
+}
+
+/// a json array of ids, or ids separated by `;` in CSV
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Ids {
+    List(Vec<u32>),
+    /// a single id in CSV reads as a number
+    One(u32),
+    Joined(String),
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let Some(path) = args.get(1) else {
+        bail!("Usage: import <file.ndjson|file.csv> [server url]");
+    };
+    let url = match args.get(2) {
+        Some(url) => url.clone(),
+        None => format!("http://[::1]:{}", AppConfig::load()?.server.port),
+    };
+
+    let path = Path::new(path);
+    let data = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let records = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => parse_csv(&data),
+        _ => parse_ndjson(&data),
+    };
+
+    // line numbers of the rows sent to the server
+    let mut lines = Vec::new();
+    let mut rows = Vec::new();
+    let mut failed = 0;
+    for (line, record) in records {
+        match record.and_then(ImportRow::try_from) {
+            Ok(row) => {
+                lines.push(line);
+                rows.push(row);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("line {}: {}", line, e);
+            }
+        }
+    }
+
+    let mut client = MetadataClient::connect(url).await?;
+    let ret = client
+        .import_contents(tokio_stream::iter(rows))
+        .await?
+        .into_inner();
+    for e in &ret.errors {
+        // the server reports the position in the stream, which maps back to a line
+        match e.row.checked_sub(1).and_then(|i| lines.get(i as usize)) {
+            Some(line) => eprintln!("line {}: {}", line, e.message),
+            None => eprintln!("row {}: {}", e.row, e.message),
+        }
+    }
+    failed += ret.errors.len();
+    println!(
+        "Imported {} publishers and {} contents, {} rows failed",
+        ret.publishers, ret.contents, failed
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// records with their line numbers, starting from 1
+fn parse_ndjson(data: &str) -> Vec<(usize, Result<Record>)> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(Into::into)))
+        .collect()
+}
+
+/// records with their line numbers, the header is line 1
+fn parse_csv(data: &str) -> Vec<(usize, Result<Record>)> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    reader
+        .deserialize()
+        .enumerate()
+        .map(|(i, record)| {
+            let line = record
+                .as_ref()
+                .err()
+                .and_then(|e| e.position())
+                .map_or(i + 2, |pos| pos.line() as usize);
+            (line, record.map_err(Into::into))
+        })
+        .collect()
+}
+
+impl TryFrom<Record> for ImportRow {
+    type Error = anyhow::Error;
+
+    fn try_from(r: Record) -> Result<Self> {
+        let row = match r.kind {
+            Kind::Publisher => Row::Publisher(Publisher {
+                id: r.id,
+                name: r.name,
+                avatar: r.avatar,
+            }),
+            Kind::Content => {
+                let name = format!("CONTENT_TYPE_{}", r.content_type.to_uppercase());
+                let content_type = match ContentType::from_str_name(&name) {
+                    Some(ContentType::Unspecified) | None => {
+                        bail!("Invalid content type: {:?}", r.content_type)
+                    }
+                    Some(t) => t,
+                };
+                let publishers = r
+                    .publisher_ids
+                    .into_ids()?
+                    .into_iter()
+                    .map(|id| Publisher {
+                        id,
+                        ..Default::default()
+                    })
+                    .collect();
+                Row::Content(Content {
+                    id: r.id,
+                    name: r.name,
+                    description: r.description,
+                    publishers,
+                    url: r.url,
+                    image: r.image,
+                    r#type: content_type as i32,
+                    created_at: r.created_at.map(|dt| Timestamp {
+                        seconds: dt.timestamp(),
+                        nanos: dt.timestamp_subsec_nanos() as i32,
+                    }),
+                    views: r.views.unwrap_or_default(),
+                    likes: r.likes.unwrap_or_default(),
+                    dislikes: r.dislikes.unwrap_or_default(),
+                    ..Default::default()
+                })
+            }
+        };
+        Ok(ImportRow { row: Some(row) })
+    }
+}
+
+impl Default for Ids {
+    fn default() -> Self {
+        Self::List(Vec::new())
+    }
+}
+
+impl Ids {
+    fn into_ids(self) -> Result<Vec<u32>> {
+        match self {
+            Self::List(ids) => Ok(ids),
+            Self::One(id) => Ok(vec![id]),
+            Self::Joined(s) => s
+                .split(';')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(|id| {
+                    id.parse()
+                        .map_err(|_| anyhow!("Invalid publisher id: {:?}", id))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ndjson_should_parse() -> Result<()> {
+        let data = r#"{"kind": "publisher", "id": 20001, "name": "Mae Runolfsson"}
+
+{"kind": "content", "id": 100, "name": "Night Train", "type": "movie", "publisher_ids": [20001]}
+{"kind": "content", "id": 101, "name": "Night Train", "type": "musical"}
+"#;
+        let records = parse_ndjson(data);
+        let lines: Vec<_> = records.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [1, 3, 4]);
+
+        let rows: Vec<_> = records
+            .into_iter()
+            .map(|(_, r)| r.and_then(ImportRow::try_from))
+            .collect();
+        assert!(
+            matches!(&rows[0], Ok(ImportRow { row: Some(Row::Publisher(p)) }) if p.id == 20001)
+        );
+        match &rows[1] {
+            Ok(ImportRow {
+                row: Some(Row::Content(c)),
+            }) => {
+                assert_eq!(c.r#type(), ContentType::Movie);
+                assert_eq!(c.publishers[0].id, 20001);
+            }
+            row => panic!("unexpected row: {:?}", row),
+        }
+        assert!(rows[2].is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn csv_should_parse() -> Result<()> {
+        let data = "kind,id,name,avatar,publisher_ids,type,created_at,views
+publisher,20001,Mae Runolfsson,https://placehold.co/400x400,,,,
+content,99,Night Bus,,20001,vlog,,
+content,100,Night Train,,20001;10001,ai_generated,2024-06-02T10:12:31Z,42
+content,x,Night Train,,,movie,,
+";
+        let records = parse_csv(data);
+        let lines: Vec<_> = records.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [2, 3, 4, 5]);
+
+        let rows: Vec<_> = records
+            .into_iter()
+            .map(|(_, r)| r.and_then(ImportRow::try_from))
+            .collect();
+        match &rows[2] {
+            Ok(ImportRow {
+                row: Some(Row::Content(c)),
+            }) => {
+                assert_eq!(c.r#type(), ContentType::AiGenerated);
+                let ids: Vec<_> = c.publishers.iter().map(|p| p.id).collect();
+                assert_eq!(ids, [20001, 10001]);
+                assert_eq!(c.views, 42);
+                assert!(c.created_at.is_some());
+            }
+            row => panic!("unexpected row: {:?}", row),
+        }
+        assert!(rows[0].is_ok());
+        assert!(rows[1].is_ok());
+        assert!(rows[3].is_err());
+        Ok(())
+    }
+}
@@ -16,6 +16,14 @@ pub struct AuthConfig {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    pub db_url: String,
+    /// seconds between writes of the engagement counted in memory, at least 1
+    #[serde(default = "default_flush_interval")]
+    pub flush_interval: u64,
+}
+
+fn default_flush_interval() -> u64 {
+    5
 }
 
 impl AppConfig {
@@ -1,6 +1,7 @@
 use anyhow::Result;
 
 use crm_metadata::{AppConfig, MetadataService};
+use tokio::signal;
 use tonic::transport::Server;
 
 #[tokio::main]
@@ -10,7 +11,32 @@ async fn main() -> Result<()> {
     let addr = format!("[::1]:{}", addr).parse().unwrap();
     println!("MetadataService listening on {}", addr);
 
-    let svc = MetadataService::new(config).into_server();
-    Server::builder().add_service(svc).serve(addr).await?;
+    let svc = MetadataService::new(config).await;
+    Server::builder()
+        .add_service(svc.clone().into_server())
+        .serve_with_shutdown(addr, shutdown_signal())
+        .await?;
+
+    // engagement counted since the last periodic flush
+    let count = svc.flush_engagement().await?;
+    println!(
+        "MetadataService flushed the engagement of {} contents",
+        count
+    );
     Ok(())
 }
+
+/// ctrl-c, or SIGTERM from the container runtime
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut term = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to listen to SIGTERM");
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = term.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = signal::ctrl_c().await;
+}
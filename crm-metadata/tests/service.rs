@@ -2,22 +2,26 @@ use std::{net::SocketAddr, time::Duration};
 
 use anyhow::Result;
 use crm_metadata::{
-    pb::{metadata_client::MetadataClient, MaterializeRequest},
+    pb::{
+        metadata_client::MetadataClient, Content, ContentType, CreateContentRequest,
+        CreatePublisherRequest, GetContentRequest, MaterializeRequest, Publisher,
+    },
     AppConfig, MetadataService,
 };
 use futures::StreamExt;
+use sqlx_db_tester::TestPg;
 use tokio::time::sleep;
 use tonic::{transport::Server, Request};
 
 #[tokio::test]
 async fn materialize_should_work() -> Result<()> {
-    let addr = start_server().await?;
+    let (_tdb, addr) = start_server().await?;
     let mut client = MetadataClient::connect(format!("http://{addr}")).await?;
 
     let stream = tokio_stream::iter(vec![
-        MaterializeRequest { id: 1 },
-        MaterializeRequest { id: 2 },
-        MaterializeRequest { id: 3 },
+        MaterializeRequest::new(1),
+        MaterializeRequest::new(2),
+        MaterializeRequest::new(3),
     ]);
 
     let req = Request::new(stream);
@@ -26,20 +30,64 @@ async fn materialize_should_work() -> Result<()> {
     let ret: Vec<_> = response.then(|res| async { res.unwrap() }).collect().await;
 
     assert_eq!(ret.len(), 3);
+    assert!(ret.into_iter().all(|res| res.into_result().is_ok()));
 
     Ok(())
 }
 
-async fn start_server() -> Result<SocketAddr> {
-    // Start the server
+#[tokio::test]
+async fn content_crud_should_work() -> Result<()> {
+    let conf = AppConfig::load()?;
+    let (_tdb, addr) = start_server_on(conf.server.port + 1).await?;
+    let mut client = MetadataClient::connect(format!("http://{addr}")).await?;
+
+    let publisher = Publisher {
+        id: 20001,
+        name: "Mae Runolfsson".to_string(),
+        avatar: "https://placehold.co/400x400".to_string(),
+    };
+    let req = CreatePublisherRequest {
+        publisher: Some(publisher.clone()),
+    };
+    client.create_publisher(req).await?;
+
+    let content = Content {
+        id: 100,
+        name: "Night Train".to_string(),
+        publishers: vec![publisher.clone()],
+        r#type: ContentType::Movie as i32,
+        ..Default::default()
+    };
+    let req = CreateContentRequest {
+        content: Some(content),
+    };
+    client.create_content(req).await?;
+
+    let ret = client
+        .get_content(GetContentRequest { id: 100 })
+        .await?
+        .into_inner();
+    assert_eq!(ret.name, "Night Train");
+    assert_eq!(ret.publishers, [publisher]);
+
+    Ok(())
+}
+
+async fn start_server() -> Result<(TestPg, SocketAddr)> {
     let conf = AppConfig::load()?;
-    let addr = SocketAddr::from(([127, 0, 0, 1], conf.server.port));
+    start_server_on(conf.server.port).await
+}
+
+async fn start_server_on(port: u16) -> Result<(TestPg, SocketAddr)> {
+    // Start the server
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
 
-    let svc = MetadataService::new(conf).into_server();
+    let (tdb, svc) = MetadataService::new_for_test().await?;
+    let svc = svc.into_server();
     tokio::spawn(async move {
         let _ = Server::builder().add_service(svc).serve(addr).await;
     });
 
     sleep(Duration::from_micros(1)).await;
-    Ok(addr)
+    Ok((tdb, addr))
 }
@@ -0,0 +1,130 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::{
+    config::{AppConfig, EmailConfig, InAppConfig, SmsConfig},
+    pb::{DeliveryStatus, EmailMessage, InAppMessage, SendResponse, SmsMessage},
+};
+
+use super::{to_ts, DeviceHub, SmtpMailer, WebhookSms};
+
+/// what became of a message handed to a delivery backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    /// the provider accepted the message, with the id it assigned if any
+    Delivered { provider_id: Option<String> },
+    /// the message may get through if it is sent again later, e.g. the provider is down
+    Retryable(String),
+    /// the message will never get through, e.g. the recipient is invalid
+    Permanent(String),
+    /// the message is kept until the recipient comes online, it is lost if the service restarts
+    Queued,
+    /// the message got through to some of the recipients only, the ones in retry_recipients
+    /// may get it if it is sent again to them, the others never will
+    Partial {
+        provider_id: Option<String>,
+        retry_recipients: Vec<String>,
+        reason: String,
+    },
+}
+
+/// delivers the messages of a channel to its provider
+#[async_trait]
+pub trait DeliveryBackend<M>: Send + Sync + 'static {
+    async fn deliver(&self, msg: &M) -> DeliveryOutcome;
+}
+
+/// the delivery backend of each channel
+pub struct Backends {
+    pub email: Box<dyn DeliveryBackend<EmailMessage>>,
+    pub sms: Box<dyn DeliveryBackend<SmsMessage>>,
+    pub in_app: Box<dyn DeliveryBackend<InAppMessage>>,
+    /// devices subscribed to in-app messages, none unless the device backend is configured
+    pub devices: Option<DeviceHub>,
+}
+
+/// logs the messages without delivering them
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DummyBackend;
+
+impl Backends {
+    pub fn new(config: &AppConfig) -> Result<Self> {
+        let email: Box<dyn DeliveryBackend<EmailMessage>> = match &config.email {
+            EmailConfig::Dummy => Box::new(DummyBackend),
+            EmailConfig::Smtp(smtp) => Box::new(SmtpMailer::new(smtp)?),
+        };
+        let sms: Box<dyn DeliveryBackend<SmsMessage>> = match &config.sms {
+            SmsConfig::Dummy => Box::new(DummyBackend),
+            SmsConfig::Webhook(webhook) => Box::new(WebhookSms::new(webhook)?),
+        };
+        let (in_app, devices): (Box<dyn DeliveryBackend<InAppMessage>>, _) = match &config.in_app {
+            InAppConfig::Dummy => (Box::new(DummyBackend), None),
+            InAppConfig::Device(device) => {
+                let devices = DeviceHub::new(device);
+                (Box::new(devices.clone()), Some(devices))
+            }
+        };
+        Ok(Self {
+            email,
+            sms,
+            in_app,
+            devices,
+        })
+    }
+}
+
+#[async_trait]
+impl<M: Debug + Send + Sync + 'static> DeliveryBackend<M> for DummyBackend {
+    async fn deliver(&self, msg: &M) -> DeliveryOutcome {
+        info!("Received message: {:?}", msg);
+        DeliveryOutcome::Delivered { provider_id: None }
+    }
+}
+
+/// deliver the message with the backend of its channel, failures are reported in the response
+pub(super) async fn deliver<M: Sync + 'static>(
+    backend: &dyn DeliveryBackend<M>,
+    msg: &M,
+    message_id: &str,
+) -> SendResponse {
+    backend.deliver(msg).await.into_response(message_id)
+}
+
+impl DeliveryOutcome {
+    pub(super) fn into_response(self, message_id: &str) -> SendResponse {
+        let (status, provider_id, reason, retry_recipients) = match self {
+            DeliveryOutcome::Delivered { provider_id } => (
+                DeliveryStatus::Delivered,
+                provider_id,
+                String::new(),
+                vec![],
+            ),
+            DeliveryOutcome::Queued => (DeliveryStatus::Queued, None, String::new(), vec![]),
+            DeliveryOutcome::Retryable(reason) => (DeliveryStatus::Retryable, None, reason, vec![]),
+            DeliveryOutcome::Permanent(reason) => (DeliveryStatus::Permanent, None, reason, vec![]),
+            DeliveryOutcome::Partial {
+                provider_id,
+                retry_recipients,
+                reason,
+            } => {
+                let status = if retry_recipients.is_empty() {
+                    DeliveryStatus::Permanent
+                } else {
+                    DeliveryStatus::Retryable
+                };
+                (status, provider_id, reason, retry_recipients)
+            }
+        };
+        SendResponse {
+            message_id: message_id.to_string(),
+            timestamp: Some(to_ts()),
+            provider_id: provider_id.unwrap_or_default(),
+            status: status as i32,
+            reason,
+            retry_recipients,
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time::{self, Instant},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{async_trait, Response, Status};
+use tracing::warn;
+
+use crate::{
+    config::DeviceConfig,
+    pb::{InAppMessage, SubscribeRequest},
+    NotificationService, ServiceResult,
+};
+
+use super::{DeliveryBackend, DeliveryOutcome};
+
+/// max wait between two evictions of the expired messages
+const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// in-app messages by device, pushed to the subscribed devices and kept in memory for the
+/// offline ones until they subscribe or the messages expire. Messages kept are lost when the
+/// service restarts. Devices are not authenticated, Subscribe must sit behind a proxy that does.
+#[derive(Debug, Clone)]
+pub struct DeviceHub {
+    max_pending: usize,
+    max_devices: usize,
+    ttl: Duration,
+    devices: Arc<Mutex<HashMap<String, Device>>>,
+}
+
+#[derive(Debug, Default)]
+struct Device {
+    subscriber: Option<mpsc::Sender<Result<InAppMessage, Status>>>,
+    pending: VecDeque<Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    msg: InAppMessage,
+    expires_at: Instant,
+}
+
+pub type DeviceStream = ReceiverStream<Result<InAppMessage, Status>>;
+
+impl NotificationService {
+    pub async fn subscribe(&self, req: SubscribeRequest) -> ServiceResult<DeviceStream> {
+        if req.device_id.is_empty() {
+            return Err(Status::invalid_argument("Device id must be specified"));
+        }
+        let Some(devices) = &self.backends.devices else {
+            return Err(Status::failed_precondition(
+                "In-app messages are not delivered to devices",
+            ));
+        };
+        Ok(Response::new(devices.subscribe(&req.device_id)))
+    }
+}
+
+impl DeviceHub {
+    /// expired messages are evicted periodically until the hub is dropped
+    pub fn new(config: &DeviceConfig) -> Self {
+        let hub = Self {
+            max_pending: config.max_pending.max(1),
+            max_devices: config.max_devices.max(1),
+            ttl: Duration::from_secs(config.pending_ttl),
+            devices: Default::default(),
+        };
+
+        let devices = Arc::downgrade(&hub.devices);
+        let mut interval = time::interval(hub.ttl.clamp(Duration::from_secs(1), EVICT_INTERVAL));
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                let Some(devices) = devices.upgrade() else {
+                    break;
+                };
+                evict_expired(&mut devices.lock().unwrap());
+            }
+        });
+        hub
+    }
+
+    /// stream of the messages of the device starting with the pending ones,
+    /// the previous subscription of the device ends
+    fn subscribe(&self, device_id: &str) -> DeviceStream {
+        let (tx, rx) = mpsc::channel(self.max_pending);
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices.entry(device_id.to_string()).or_default();
+        // the channel can hold all of them
+        let now = Instant::now();
+        for pending in device.pending.drain(..) {
+            if pending.expires_at > now {
+                tx.try_send(Ok(pending.msg))
+                    .expect("pending messages exceed the channel");
+            }
+        }
+        device.subscriber = Some(tx.clone());
+
+        let hub = self.clone();
+        let device_id = device_id.to_string();
+        tokio::spawn(async move {
+            tx.closed().await;
+            hub.remove_idle(&device_id, &tx);
+        });
+        ReceiverStream::new(rx)
+    }
+
+    /// forget the device once the subscriber went offline, unless messages were kept for it
+    /// or it subscribed again
+    fn remove_idle(
+        &self,
+        device_id: &str,
+        subscriber: &mpsc::Sender<Result<InAppMessage, Status>>,
+    ) {
+        let mut devices = self.devices.lock().unwrap();
+        let idle = devices.get(device_id).is_some_and(|device| {
+            device.pending.is_empty()
+                && device
+                    .subscriber
+                    .as_ref()
+                    .is_some_and(|s| s.same_channel(subscriber))
+        });
+        if idle {
+            devices.remove(device_id);
+        }
+    }
+}
+
+#[async_trait]
+impl DeliveryBackend<InAppMessage> for DeviceHub {
+    async fn deliver(&self, msg: &InAppMessage) -> DeliveryOutcome {
+        let mut devices = self.devices.lock().unwrap();
+        if !devices.contains_key(&msg.device_id) && devices.len() >= self.max_devices {
+            evict_expired(&mut devices);
+            if devices.len() >= self.max_devices {
+                return DeliveryOutcome::Retryable(format!(
+                    "Too many devices to keep a message for {}",
+                    msg.device_id
+                ));
+            }
+        }
+        let device = devices.entry(msg.device_id.clone()).or_default();
+        if let Some(subscriber) = &device.subscriber {
+            match subscriber.try_send(Ok(msg.clone())) {
+                Ok(_) => return DeliveryOutcome::Delivered { provider_id: None },
+                Err(TrySendError::Full(_)) => {
+                    return DeliveryOutcome::Retryable(format!(
+                        "Device {} is not keeping up",
+                        msg.device_id
+                    ))
+                }
+                // the device went offline
+                Err(TrySendError::Closed(_)) => device.subscriber = None,
+            }
+        }
+
+        if device.pending.len() >= self.max_pending {
+            if let Some(dropped) = device.pending.pop_front() {
+                warn!(
+                    "Drop message {} of offline device {}",
+                    dropped.msg.message_id, msg.device_id
+                );
+            }
+        }
+        device.pending.push_back(Pending {
+            msg: msg.clone(),
+            expires_at: Instant::now() + self.ttl,
+        });
+        DeliveryOutcome::Queued
+    }
+}
+
+/// drop the expired messages and the offline devices left without messages
+fn evict_expired(devices: &mut HashMap<String, Device>) {
+    let now = Instant::now();
+    devices.retain(|_, device| {
+        device.pending.retain(|pending| pending.expires_at > now);
+        device.subscriber.is_some() || !device.pending.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn device_should_get_pending_messages_then_live_ones() {
+        let hub = hub(2);
+        let messages: Vec<_> = (0..4).map(|i| message("phone", i)).collect();
+
+        // the oldest message is dropped while the device is offline
+        for msg in &messages[..3] {
+            hub.deliver(msg).await;
+        }
+        let mut stream = hub.subscribe("phone");
+        assert_eq!(next_id(&mut stream).await, "1");
+        assert_eq!(next_id(&mut stream).await, "2");
+
+        hub.deliver(&messages[3]).await;
+        assert_eq!(next_id(&mut stream).await, "3");
+
+        // other devices are not affected
+        let msg = message("tablet", 4);
+        hub.deliver(&msg).await;
+        assert_eq!(hub.devices.lock().unwrap()["tablet"].pending.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_messages_should_be_evicted() {
+        let hub = DeviceHub::new(&DeviceConfig {
+            max_pending: 2,
+            max_devices: 1,
+            pending_ttl: 0,
+        });
+        assert_eq!(
+            hub.deliver(&message("phone", 0)).await,
+            DeliveryOutcome::Queued
+        );
+
+        // the expired message of the phone makes room for the tablet
+        assert_eq!(
+            hub.deliver(&message("tablet", 1)).await,
+            DeliveryOutcome::Queued
+        );
+        assert!(!hub.devices.lock().unwrap().contains_key("phone"));
+
+        // nothing is left when the tablet subscribes
+        let mut stream = hub.subscribe("tablet");
+        hub.deliver(&message("tablet", 2)).await;
+        assert_eq!(next_id(&mut stream).await, "2");
+    }
+
+    #[tokio::test]
+    async fn messages_to_too_many_devices_should_be_retryable() {
+        let hub = DeviceHub::new(&DeviceConfig {
+            max_pending: 2,
+            max_devices: 1,
+            pending_ttl: 60,
+        });
+        assert_eq!(
+            hub.deliver(&message("phone", 0)).await,
+            DeliveryOutcome::Queued
+        );
+        assert!(matches!(
+            hub.deliver(&message("tablet", 1)).await,
+            DeliveryOutcome::Retryable(_)
+        ));
+
+        // known devices still get their messages
+        let mut stream = hub.subscribe("phone");
+        assert_eq!(
+            hub.deliver(&message("phone", 2)).await,
+            DeliveryOutcome::Delivered { provider_id: None }
+        );
+        assert_eq!(next_id(&mut stream).await, "0");
+        assert_eq!(next_id(&mut stream).await, "2");
+    }
+
+    #[tokio::test]
+    async fn messages_should_be_kept_once_the_device_disconnects() {
+        let hub = hub(2);
+        drop(hub.subscribe("phone"));
+
+        hub.deliver(&message("phone", 0)).await;
+        let mut stream = hub.subscribe("phone");
+        assert_eq!(next_id(&mut stream).await, "0");
+    }
+
+    #[tokio::test]
+    async fn device_should_be_removed_once_it_disconnects() {
+        let hub = hub(2);
+        let stream = hub.subscribe("phone");
+        drop(hub.subscribe("tablet"));
+        wait_until_removed(&hub, "tablet").await;
+        assert!(hub.devices.lock().unwrap().contains_key("phone"));
+
+        // kept while a message waits for it
+        drop(stream);
+        hub.deliver(&message("phone", 0)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(hub.devices.lock().unwrap()["phone"].pending.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn subscribe_should_fail_without_the_device_backend() -> anyhow::Result<()> {
+        // send.yml leaves the device backend off
+        let svc = NotificationService::new(crate::AppConfig::load()?);
+        let req = SubscribeRequest {
+            device_id: "phone".to_string(),
+        };
+        let err = svc.subscribe(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        Ok(())
+    }
+
+    async fn wait_until_removed(hub: &DeviceHub, device_id: &str) {
+        for _ in 0..100 {
+            if !hub.devices.lock().unwrap().contains_key(device_id) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("device {} was not removed", device_id);
+    }
+
+    fn hub(max_pending: usize) -> DeviceHub {
+        DeviceHub::new(&DeviceConfig {
+            max_pending,
+            max_devices: 10,
+            pending_ttl: 60,
+        })
+    }
+
+    fn message(device_id: &str, i: u32) -> InAppMessage {
+        InAppMessage {
+            message_id: i.to_string(),
+            device_id: device_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn next_id(stream: &mut DeviceStream) -> String {
+        stream.next().await.unwrap().unwrap().message_id
+    }
+}
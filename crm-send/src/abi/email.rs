@@ -1,24 +1,13 @@
-use tonic::Status;
-
 use crate::{
     pb::{send_request::Msg, EmailMessage, SendRequest, SendResponse},
     NotificationService,
 };
 
-use super::{to_ts, Sender};
+use super::{backend::deliver, Sender};
 
 impl Sender for EmailMessage {
-    async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender
-            .send(Msg::Email(self))
-            .await
-            .map_err(|e| Status::internal(format!("Failed to send email: {}", e)))?;
-
-        Ok(SendResponse {
-            message_id,
-            timestamp: Some(to_ts()),
-        })
+    async fn send(self, svc: NotificationService) -> SendResponse {
+        deliver(svc.backends.email.as_ref(), &self, &self.message_id).await
     }
 }
 
@@ -1,23 +1,13 @@
-use tonic::Status;
-
 use crate::{
     pb::{send_request::Msg, InAppMessage, SendRequest, SendResponse},
     NotificationService,
 };
 
-use super::{to_ts, Sender};
+use super::{backend::deliver, Sender};
 
 impl Sender for InAppMessage {
-    async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender
-            .send(Msg::InApp(self))
-            .await
-            .map_err(|e| Status::internal(format!("Failed to send in-app message: {}", e)))?;
-        Ok(SendResponse {
-            message_id,
-            timestamp: Some(to_ts()),
-        })
+    async fn send(self, svc: NotificationService) -> SendResponse {
+        deliver(svc.backends.in_app.as_ref(), &self, &self.message_id).await
     }
 }
 
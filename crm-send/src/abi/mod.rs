@@ -1,16 +1,26 @@
+mod backend;
+mod device;
 mod email;
 mod in_app;
 mod sms;
+mod smtp;
+mod webhook;
+
+pub use backend::{Backends, DeliveryBackend, DeliveryOutcome, DummyBackend};
+pub use device::DeviceHub;
+pub use smtp::SmtpMailer;
+pub use webhook::WebhookSms;
+
 use crm_metadata::{abi::Tpl, pb::Content};
-use std::{ops::Deref, sync::Arc, time::Duration};
+use std::{collections::HashMap, ops::Deref, sync::Arc};
 
 use chrono::Utc;
-use futures::{Stream, StreamExt};
+use futures::{future, Stream, StreamExt};
 use prost_types::Timestamp;
-use tokio::{sync::mpsc, time::sleep};
+use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Response, Status};
-use tracing::{info, warn};
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
@@ -25,13 +35,18 @@ use crate::{
 const CHANNEL_SIZE: usize = 1024;
 
 pub trait Sender {
-    async fn send(self, svc: NotificationService) -> Result<SendResponse, Status>;
+    async fn send(self, svc: NotificationService) -> SendResponse;
 }
 
 impl NotificationService {
     pub fn new(config: AppConfig) -> Self {
-        let sender = dummy_send();
-        let inner = NotificationServiceInner { config, sender };
+        let backends = Backends::new(&config).expect("failed to create delivery backends");
+        Self::new_with_backends(config, backends)
+    }
+
+    /// service delivering with the given backends instead of the configured ones
+    pub fn new_with_backends(config: AppConfig, backends: Backends) -> Self {
+        let inner = NotificationServiceInner { config, backends };
         Self {
             inner: Arc::new(inner),
         }
@@ -43,48 +58,65 @@ impl NotificationService {
 
     pub async fn send(
         &self,
-        mut stream: impl Stream<Item = Result<SendRequest, Status>> + Send + 'static + Unpin,
+        stream: impl Stream<Item = Result<SendRequest, Status>> + Send + 'static + Unpin,
     ) -> ServiceResult<ResponseStream> {
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
         let notification = self.clone();
+        let concurrency = self.config.server.concurrency.max(1);
         tokio::spawn(async move {
-            while let Some(Ok(req)) = stream.next().await {
-                let notification_clone = notification.clone();
-                let res = match req.msg {
-                    Some(Msg::Email(email)) => email.send(notification_clone).await,
-                    Some(Msg::Sms(sms)) => sms.send(notification_clone).await,
-                    Some(Msg::InApp(in_app)) => in_app.send(notification_clone).await,
-                    None => {
-                        warn!("No message type specified");
-                        Err(Status::invalid_argument("No message type specified"))
-                    }
-                };
-                tx.send(res).await.unwrap();
+            // the responses come in the order the messages are delivered
+            let mut responses = stream
+                .take_while(|req| future::ready(req.is_ok()))
+                .filter_map(|req| future::ready(req.ok()))
+                .map(|req| notification.clone().send_one(req))
+                .buffer_unordered(concurrency);
+            while let Some(res) = responses.next().await {
+                // the client is gone, the remaining messages are not delivered
+                if tx.send(Ok(res)).await.is_err() {
+                    break;
+                }
             }
         });
 
         let stream = ReceiverStream::new(rx);
         Ok(Response::new(Box::pin(stream)))
     }
+
+    async fn send_one(self, req: SendRequest) -> SendResponse {
+        match req.msg {
+            Some(Msg::Email(email)) => email.send(self).await,
+            Some(Msg::Sms(sms)) => sms.send(self).await,
+            Some(Msg::InApp(in_app)) => in_app.send(self).await,
+            None => {
+                warn!("No message type specified");
+                DeliveryOutcome::Permanent("No message type specified".to_string())
+                    .into_response("")
+            }
+        }
+    }
 }
 
 impl SendRequest {
+    /// email rendered from the template for the user, user variables like name are
+    /// available to the template
     pub fn new(
-        subject: String,
+        tpl: &Tpl,
         sender: String,
         recipients: &[String],
         contents: &[Content],
-    ) -> Self {
-        let tpl = Tpl(contents);
+        user: &HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let body = tpl.render(contents, user)?;
         let msg = Msg::Email(EmailMessage {
             message_id: Uuid::new_v4().to_string(),
-            subject,
+            subject: body.subject,
             sender,
             recipients: recipients.to_vec(),
-            body: tpl.to_body(),
+            body: body.text,
+            html_body: body.html,
         });
 
-        SendRequest { msg: Some(msg) }
+        Ok(SendRequest { msg: Some(msg) })
     }
 }
 
@@ -103,28 +135,41 @@ impl Deref for NotificationService {
     }
 }
 
-fn dummy_send() -> mpsc::Sender<Msg> {
-    let (tx, mut rx) = mpsc::channel(CHANNEL_SIZE * 100);
-
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            info!("Received message: {:?}", msg);
-            sleep(Duration::from_millis(300)).await;
-        }
-    });
-    tx
-}
-
 #[cfg(test)]
 mod tests {
+    use std::{collections::HashMap, time::Duration};
+
     use anyhow::Result;
+    use tokio::{sync::Barrier, time::timeout};
     use tokio_stream::StreamExt;
+    use tonic::async_trait;
 
     use crate::{
-        pb::{EmailMessage, InAppMessage, SmsMessage},
-        AppConfig, NotificationService,
+        pb::{DeliveryStatus, EmailMessage, InAppMessage, SmsMessage},
+        AppConfig, Backends, DeliveryBackend, DeliveryOutcome, DummyBackend, NotificationService,
     };
 
+    /// rejects every SMS
+    struct RejectingSms;
+
+    #[async_trait]
+    impl DeliveryBackend<SmsMessage> for RejectingSms {
+        async fn deliver(&self, _msg: &SmsMessage) -> DeliveryOutcome {
+            DeliveryOutcome::Permanent("invalid number".to_string())
+        }
+    }
+
+    /// holds every email until the given number of them are being delivered
+    struct GatheringEmail(Barrier);
+
+    #[async_trait]
+    impl DeliveryBackend<EmailMessage> for GatheringEmail {
+        async fn deliver(&self, _msg: &EmailMessage) -> DeliveryOutcome {
+            self.0.wait().await;
+            DeliveryOutcome::Delivered { provider_id: None }
+        }
+    }
+
     #[tokio::test]
     async fn send_should_work() -> Result<()> {
         let conf = AppConfig::load()?;
@@ -139,4 +184,57 @@ mod tests {
         assert_eq!(ret.len(), 3);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn send_should_use_the_backend_of_each_channel() -> Result<()> {
+        let backends = Backends {
+            email: Box::new(DummyBackend),
+            sms: Box::new(RejectingSms),
+            in_app: Box::new(DummyBackend),
+            devices: None,
+        };
+        let service = NotificationService::new_with_backends(AppConfig::load()?, backends);
+        let email = EmailMessage::fake();
+        let sms = SmsMessage::fake();
+        let in_app = InAppMessage::fake();
+        // a rejected message does not end the stream
+        let stream = tokio_stream::iter(vec![
+            Ok(email.clone().into()),
+            Ok(sms.clone().into()),
+            Ok(in_app.clone().into()),
+        ]);
+        let res = service.send(stream).await?;
+        let ret = res.into_inner().collect::<Result<Vec<_>, _>>().await?;
+        let ret: HashMap<_, _> = ret
+            .into_iter()
+            .map(|res| (res.message_id.clone(), res))
+            .collect();
+        assert_eq!(ret.len(), 3);
+        assert_eq!(ret[&email.message_id].status(), DeliveryStatus::Delivered);
+        assert_eq!(ret[&in_app.message_id].status(), DeliveryStatus::Delivered);
+        let rejected = &ret[&sms.message_id];
+        assert_eq!(rejected.status(), DeliveryStatus::Permanent);
+        assert_eq!(rejected.reason, "invalid number");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_should_deliver_messages_concurrently() -> Result<()> {
+        let backends = Backends {
+            email: Box::new(GatheringEmail(Barrier::new(3))),
+            sms: Box::new(DummyBackend),
+            in_app: Box::new(DummyBackend),
+            devices: None,
+        };
+        let service = NotificationService::new_with_backends(AppConfig::load()?, backends);
+        let stream = tokio_stream::iter(vec![
+            Ok(EmailMessage::fake().into()),
+            Ok(EmailMessage::fake().into()),
+            Ok(EmailMessage::fake().into()),
+        ]);
+        let res = service.send(stream).await?;
+        let ret = timeout(Duration::from_secs(5), res.into_inner().collect::<Vec<_>>()).await?;
+        assert_eq!(ret.len(), 3);
+        Ok(())
+    }
 }
@@ -1,23 +1,13 @@
-use tonic::Status;
-
 use crate::{
     pb::{send_request::Msg, SendRequest, SendResponse, SmsMessage},
     NotificationService,
 };
 
-use super::{to_ts, Sender};
+use super::{backend::deliver, Sender};
 
 impl Sender for SmsMessage {
-    async fn send(self, svc: NotificationService) -> Result<SendResponse, Status> {
-        let message_id = self.message_id.clone();
-        svc.sender
-            .send(Msg::Sms(self))
-            .await
-            .map_err(|e| Status::internal(format!("Failed to send SMS: {}", e)))?;
-        Ok(SendResponse {
-            message_id,
-            timestamp: Some(to_ts()),
-        })
+    async fn send(self, svc: NotificationService) -> SendResponse {
+        deliver(svc.backends.sms.as_ref(), &self, &self.message_id).await
     }
 }
 
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use lettre::{
+    message::{header::ContentType, Mailbox, MultiPart},
+    transport::smtp::{authentication::Credentials, PoolConfig},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tonic::async_trait;
+
+use crate::{
+    config::{SmtpConfig, SmtpTls},
+    pb::EmailMessage,
+};
+
+use super::{DeliveryBackend, DeliveryOutcome};
+
+/// delivers emails to an SMTP relay over a pool of connections
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    /// connections are opened lazily on the first email
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        let mut builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        };
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+            }
+            (None, None) => {}
+            _ => bail!("SMTP username and password must be set together"),
+        }
+        let transport = builder
+            .pool_config(PoolConfig::new().max_size(config.pool_size))
+            .timeout(Some(Duration::from_secs(config.timeout)))
+            .build();
+        Ok(Self { transport })
+    }
+}
+
+#[async_trait]
+impl DeliveryBackend<EmailMessage> for SmtpMailer {
+    async fn deliver(&self, email: &EmailMessage) -> DeliveryOutcome {
+        let message = match to_message(email) {
+            Ok(message) => message,
+            Err(e) => return DeliveryOutcome::Permanent(format!("Invalid email: {}", e)),
+        };
+        match self.transport.send(message).await {
+            Ok(_) => DeliveryOutcome::Delivered { provider_id: None },
+            // the relay won't take the email however many times it is retried
+            Err(e) if e.is_permanent() => DeliveryOutcome::Permanent(e.to_string()),
+            Err(e) => DeliveryOutcome::Retryable(e.to_string()),
+        }
+    }
+}
+
+fn to_message(email: &EmailMessage) -> Result<Message> {
+    let mut builder = Message::builder()
+        .message_id(Some(format!("<{}@crm-send>", email.message_id)))
+        .from(email.sender.parse::<Mailbox>()?)
+        .subject(&email.subject);
+    for recipient in &email.recipients {
+        builder = builder.to(recipient.parse::<Mailbox>()?);
+    }
+
+    let message = if email.html_body.is_empty() {
+        builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())?
+    } else {
+        builder.multipart(MultiPart::alternative_plain_html(
+            email.body.clone(),
+            email.html_body.clone(),
+        ))?
+    };
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the pool of the transport is spawned on the runtime
+    #[tokio::test]
+    async fn half_set_credentials_should_be_rejected() {
+        let mut config = SmtpConfig {
+            host: "localhost".to_string(),
+            port: None,
+            tls: SmtpTls::None,
+            username: Some("crm".to_string()),
+            password: None,
+            pool_size: 1,
+            timeout: 5,
+        };
+        assert!(SmtpMailer::new(&config).is_err());
+
+        config.password = Some("secret".to_string());
+        assert!(SmtpMailer::new(&config).is_ok());
+
+        config.username = None;
+        assert!(SmtpMailer::new(&config).is_err());
+    }
+
+    #[test]
+    fn to_message_should_work() -> Result<()> {
+        let email = EmailMessage {
+            message_id: "42".to_string(),
+            sender: "crm@example.com".to_string(),
+            recipients: vec!["a@example.com".to_string(), "b@example.com".to_string()],
+            subject: "Welcome".to_string(),
+            body: "Hi".to_string(),
+            html_body: "<p>Hi</p>".to_string(),
+        };
+        let message = String::from_utf8(to_message(&email)?.formatted())?;
+        assert!(message.contains("Message-ID: <42@crm-send>"));
+        assert!(message.contains("To: a@example.com, b@example.com"));
+        assert!(message.contains("multipart/alternative"));
+
+        let email = EmailMessage {
+            recipients: vec!["not an address".to_string()],
+            ..email
+        };
+        assert!(to_message(&email).is_err());
+        Ok(())
+    }
+}
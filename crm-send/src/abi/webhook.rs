@@ -0,0 +1,368 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Result;
+use minijinja::{context, Environment};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, StatusCode,
+};
+use serde_json::{Map, Value};
+use tonic::async_trait;
+
+use crate::{
+    config::{WebhookConfig, WebhookFormat},
+    pb::SmsMessage,
+};
+
+use super::{DeliveryBackend, DeliveryOutcome};
+
+/// posts SMS to the HTTP API of a provider, a request for every recipient
+pub struct WebhookSms {
+    client: Client,
+    url: String,
+    format: WebhookFormat,
+    /// a template for every field of the request body, named after the field
+    env: Environment<'static>,
+    fields: Vec<String>,
+    username: Option<String>,
+    password: Option<String>,
+    id_pointer: String,
+    error_code_pointer: Option<String>,
+    retryable_codes: HashSet<String>,
+}
+
+impl WebhookSms {
+    pub fn new(config: &WebhookConfig) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.insert(HeaderName::try_from(name)?, HeaderValue::try_from(value)?);
+        }
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_secs(config.timeout))
+            .build()?;
+
+        let mut env = Environment::new();
+        for (name, value) in &config.fields {
+            env.add_template_owned(name.clone(), value.clone())?;
+        }
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            format: config.format,
+            env,
+            fields: config.fields.keys().cloned().collect(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+            id_pointer: config.id_pointer.clone(),
+            error_code_pointer: config.error_code_pointer.clone(),
+            retryable_codes: config.retryable_codes.iter().cloned().collect(),
+        })
+    }
+
+    fn render(&self, sms: &SmsMessage, recipient: &str) -> Result<Map<String, Value>> {
+        let ctx = context! {
+            sender => &sms.sender,
+            recipient => recipient,
+            body => &sms.body,
+            message_id => &sms.message_id,
+        };
+        self.fields
+            .iter()
+            .map(|name| {
+                let value = self.env.get_template(name)?.render(&ctx)?;
+                Ok((name.clone(), Value::String(value)))
+            })
+            .collect()
+    }
+
+    async fn deliver_to(&self, sms: &SmsMessage, recipient: &str) -> DeliveryOutcome {
+        let body = match self.render(sms, recipient) {
+            Ok(body) => body,
+            Err(e) => return DeliveryOutcome::Permanent(format!("Invalid request body: {}", e)),
+        };
+        let mut req = self.client.post(&self.url);
+        if let Some(username) = &self.username {
+            req = req.basic_auth(username, self.password.as_ref());
+        }
+        req = match self.format {
+            WebhookFormat::Json => req.json(&body),
+            WebhookFormat::Form => req.form(&body),
+        };
+
+        let res = match req.send().await {
+            Ok(res) => res,
+            Err(e) => return DeliveryOutcome::Retryable(format!("{}: {}", recipient, e)),
+        };
+        let status = res.status();
+        let text = res.text().await.unwrap_or_default();
+        let json: Option<Value> = serde_json::from_str(&text).ok();
+        if status.is_success() {
+            let provider_id = json
+                .as_ref()
+                .and_then(|json| pointer_str(json, &self.id_pointer));
+            return DeliveryOutcome::Delivered { provider_id };
+        }
+
+        let code = self
+            .error_code_pointer
+            .as_ref()
+            .zip(json.as_ref())
+            .and_then(|(pointer, json)| pointer_str(json, pointer));
+        let reason = match &code {
+            Some(code) => format!("{}: {} with code {}: {}", recipient, status, code, text),
+            None => format!("{}: {}: {}", recipient, status, text),
+        };
+        if self.is_retryable(status, code.as_deref()) {
+            DeliveryOutcome::Retryable(reason)
+        } else {
+            DeliveryOutcome::Permanent(reason)
+        }
+    }
+
+    /// timeouts, throttling and server errors are always retried, other errors only if the
+    /// provider code is a retryable one
+    fn is_retryable(&self, status: StatusCode, code: Option<&str>) -> bool {
+        status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status.is_server_error()
+            || code.is_some_and(|code| self.retryable_codes.contains(code))
+    }
+}
+
+#[async_trait]
+impl DeliveryBackend<SmsMessage> for WebhookSms {
+    /// sent to every recipient even if some fail, so that a retry only goes to the failed
+    /// ones. The provider ids are joined by commas.
+    async fn deliver(&self, sms: &SmsMessage) -> DeliveryOutcome {
+        if sms.recipients.is_empty() {
+            return DeliveryOutcome::Permanent("No recipient".to_string());
+        }
+        let mut ids = Vec::with_capacity(sms.recipients.len());
+        let mut delivered = 0;
+        let mut retry_recipients = Vec::new();
+        let mut reasons = Vec::new();
+        for recipient in &sms.recipients {
+            match self.deliver_to(sms, recipient).await {
+                DeliveryOutcome::Delivered { provider_id } => {
+                    delivered += 1;
+                    ids.extend(provider_id);
+                }
+                DeliveryOutcome::Queued => delivered += 1,
+                DeliveryOutcome::Retryable(reason) => {
+                    retry_recipients.push(recipient.clone());
+                    reasons.push(reason);
+                }
+                DeliveryOutcome::Permanent(reason) | DeliveryOutcome::Partial { reason, .. } => {
+                    reasons.push(reason)
+                }
+            }
+        }
+
+        let provider_id = (!ids.is_empty()).then(|| ids.join(","));
+        let reason = reasons.join("; ");
+        if reasons.is_empty() {
+            DeliveryOutcome::Delivered { provider_id }
+        } else if delivered == 0 && retry_recipients.is_empty() {
+            DeliveryOutcome::Permanent(reason)
+        } else if retry_recipients.len() == sms.recipients.len() {
+            DeliveryOutcome::Retryable(reason)
+        } else {
+            DeliveryOutcome::Partial {
+                provider_id,
+                retry_recipients,
+                reason,
+            }
+        }
+    }
+}
+
+/// the value at the json pointer as a string, numbers are formatted
+fn pointer_str(json: &Value, pointer: &str) -> Option<String> {
+    match json.pointer(pointer)? {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        v => Some(v.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn webhook_should_post_json_and_parse_the_message_id() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/messages"))
+            .and(header("x-api-key", "secret"))
+            .and(body_json(json!({"to": "+15550100", "text": "Hi"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"data": {"id": 42}})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut config = config(&server, WebhookFormat::Json);
+        config.headers.insert("x-api-key".into(), "secret".into());
+        config.id_pointer = "/data/id".to_string();
+        let backend = WebhookSms::new(&config)?;
+        let outcome = backend.deliver(&sms(&["+15550100"])).await;
+        assert_eq!(
+            outcome,
+            DeliveryOutcome::Delivered {
+                provider_id: Some("42".to_string())
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn webhook_should_post_form_with_basic_auth() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("authorization", "Basic QUMxMjM6dG9rZW4="))
+            .and(body_string_contains("to=%2B15550100"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": "SM1"})))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let mut config = config(&server, WebhookFormat::Form);
+        config.username = Some("AC123".to_string());
+        config.password = Some("token".to_string());
+        let backend = WebhookSms::new(&config)?;
+        let outcome = backend.deliver(&sms(&["+15550100", "+15550100"])).await;
+        assert_eq!(
+            outcome,
+            DeliveryOutcome::Delivered {
+                provider_id: Some("SM1,SM1".to_string())
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn webhook_errors_should_be_retryable_or_permanent() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(path("/throttled"))
+            .respond_with(ResponseTemplate::new(429))
+            .mount(&server)
+            .await;
+        Mock::given(path("/invalid"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({"code": 21211})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/queue_full"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({"code": 30001})))
+            .mount(&server)
+            .await;
+        Mock::given(path("/down"))
+            .respond_with(ResponseTemplate::new(503).set_body_json(json!({"code": 21211})))
+            .mount(&server)
+            .await;
+
+        let mut config = config(&server, WebhookFormat::Json);
+        config.error_code_pointer = Some("/code".to_string());
+        config.retryable_codes = vec!["30001".to_string()];
+        for (path, retryable) in [
+            ("throttled", true),
+            ("invalid", false),
+            ("queue_full", true),
+            // server errors are retried whatever the code
+            ("down", true),
+        ] {
+            config.url = format!("{}/{}", server.uri(), path);
+            let outcome = WebhookSms::new(&config)?
+                .deliver(&sms(&["+15550100"]))
+                .await;
+            assert_eq!(
+                matches!(outcome, DeliveryOutcome::Retryable(_)),
+                retryable,
+                "{}: {:?}",
+                path,
+                outcome
+            );
+        }
+
+        // nobody is listening
+        config.url = "http://127.0.0.1:1/messages".to_string();
+        let outcome = WebhookSms::new(&config)?
+            .deliver(&sms(&["+15550100"]))
+            .await;
+        assert!(matches!(outcome, DeliveryOutcome::Retryable(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn webhook_should_retry_the_failed_recipients_only() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(body_json(json!({"to": "+15550100", "text": "Hi"})))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": "SM1"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(body_json(json!({"to": "+15550101", "text": "Hi"})))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        Mock::given(body_json(json!({"to": "+15550102", "text": "Hi"})))
+            .respond_with(ResponseTemplate::new(400))
+            .mount(&server)
+            .await;
+
+        let backend = WebhookSms::new(&config(&server, WebhookFormat::Json))?;
+        let outcome = backend
+            .deliver(&sms(&["+15550100", "+15550101", "+15550102"]))
+            .await;
+        let DeliveryOutcome::Partial {
+            provider_id,
+            retry_recipients,
+            ..
+        } = outcome
+        else {
+            panic!("expected a partial delivery: {:?}", outcome);
+        };
+        assert_eq!(provider_id.as_deref(), Some("SM1"));
+        assert_eq!(retry_recipients, ["+15550101"]);
+
+        // nothing got through and nothing will
+        let outcome = backend.deliver(&sms(&["+15550102"])).await;
+        assert!(matches!(outcome, DeliveryOutcome::Permanent(_)));
+        Ok(())
+    }
+
+    fn config(server: &MockServer, format: WebhookFormat) -> WebhookConfig {
+        let fields = BTreeMap::from([
+            ("to".to_string(), "{{ recipient }}".to_string()),
+            ("text".to_string(), "{{ body }}".to_string()),
+        ]);
+        WebhookConfig {
+            url: format!("{}/messages", server.uri()),
+            format,
+            fields,
+            headers: BTreeMap::new(),
+            username: None,
+            password: None,
+            id_pointer: "/id".to_string(),
+            error_code_pointer: None,
+            retryable_codes: Vec::new(),
+            timeout: 5,
+        }
+    }
+
+    fn sms(recipients: &[&str]) -> SmsMessage {
+        SmsMessage {
+            message_id: "1".to_string(),
+            sender: "+15550199".to_string(),
+            recipients: recipients.iter().map(|r| r.to_string()).collect(),
+            body: "Hi".to_string(),
+        }
+    }
+}
@@ -1,11 +1,20 @@
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
-use std::{env, fs::File};
+use std::{collections::BTreeMap, env, fs::File};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub auth: AuthConfig,
+    /// how emails are delivered, logged only if not configured
+    #[serde(default)]
+    pub email: EmailConfig,
+    /// how SMS are delivered, logged only if not configured
+    #[serde(default)]
+    pub sms: SmsConfig,
+    /// how in-app messages are delivered, logged only if not configured
+    #[serde(default)]
+    pub in_app: InAppConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,6 +25,152 @@ pub struct AuthConfig {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    /// max number of messages of a Send stream being delivered at once
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum EmailConfig {
+    /// log the emails without delivering them
+    #[default]
+    Dummy,
+    /// deliver the emails to an SMTP relay
+    Smtp(SmtpConfig),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum SmsConfig {
+    /// log the SMS without delivering them
+    #[default]
+    Dummy,
+    /// post the SMS to the HTTP API of a provider
+    Webhook(Box<WebhookConfig>),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum InAppConfig {
+    /// log the in-app messages without delivering them
+    #[default]
+    Dummy,
+    /// push the in-app messages to the subscribed devices
+    Device(DeviceConfig),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    /// max number of messages kept for an offline device, the oldest ones are dropped
+    #[serde(default = "default_max_pending")]
+    pub max_pending: usize,
+    /// max number of devices kept at once, messages to other devices are retryable
+    #[serde(default = "default_max_devices")]
+    pub max_devices: usize,
+    /// seconds a message is kept for an offline device
+    #[serde(default = "default_pending_ttl")]
+    pub pending_ttl: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// defaults to 25, 587 or 465 depending on tls
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    /// login to the relay, username and password are set together or not at all
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// max number of connections kept open to the relay
+    #[serde(default = "default_pool_size")]
+    pub pool_size: u32,
+    /// seconds to wait for the relay before giving up
+    #[serde(default = "default_smtp_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// plain text, only for local relays
+    None,
+    /// upgrade the connection with STARTTLS
+    #[default]
+    Starttls,
+    /// TLS from the start, a.k.a. SMTPS
+    Tls,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// fields of the request body, the values are templates of sender, recipient, body
+    /// and message_id. A request is sent for every recipient.
+    pub fields: BTreeMap<String, String>,
+    /// extra headers such as an API key
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// basic auth, e.g. the account sid and auth token of Twilio
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// json pointer of the message id in a successful response, e.g. /sid
+    #[serde(default = "default_id_pointer")]
+    pub id_pointer: String,
+    /// json pointer of the error code in a failed response, e.g. /code
+    pub error_code_pointer: Option<String>,
+    /// provider error codes worth retrying, other codes are permanent failures.
+    /// 408, 429 and 5xx are retried whatever the code.
+    #[serde(default)]
+    pub retryable_codes: Vec<String>,
+    /// seconds to wait for the provider before giving up
+    #[serde(default = "default_webhook_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// application/json
+    #[default]
+    Json,
+    /// application/x-www-form-urlencoded
+    Form,
+}
+
+fn default_concurrency() -> usize {
+    10
+}
+
+fn default_pool_size() -> u32 {
+    10
+}
+
+fn default_smtp_timeout() -> u64 {
+    30
+}
+
+fn default_max_pending() -> usize {
+    100
+}
+
+fn default_max_devices() -> usize {
+    10000
+}
+
+fn default_pending_ttl() -> u64 {
+    86400
+}
+
+fn default_id_pointer() -> String {
+    "/id".to_string()
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
 }
 
 impl AppConfig {
@@ -1,10 +1,17 @@
 use std::{pin::Pin, sync::Arc};
 
-pub use config::AppConfig;
+pub use abi::{
+    Backends, DeliveryBackend, DeliveryOutcome, DeviceHub, DummyBackend, SmtpMailer, WebhookSms,
+};
+pub use config::{
+    AppConfig, DeviceConfig, EmailConfig, InAppConfig, SmsConfig, SmtpConfig, SmtpTls,
+    WebhookConfig, WebhookFormat,
+};
 use futures::Stream;
-use pb::{notification_server::Notification, send_request::Msg, SendRequest, SendResponse};
+use pb::{
+    notification_server::Notification, InAppMessage, SendRequest, SendResponse, SubscribeRequest,
+};
 
-use tokio::sync::mpsc::Sender;
 use tonic::{async_trait, Request, Response, Status, Streaming};
 
 mod abi;
@@ -20,15 +27,17 @@ pub struct NotificationService {
 #[allow(dead_code)]
 pub struct NotificationServiceInner {
     config: AppConfig,
-    sender: Sender<Msg>,
+    backends: Backends,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
 type ResponseStream = Pin<Box<dyn Stream<Item = Result<SendResponse, Status>> + Send>>;
+type SubscribeStream = Pin<Box<dyn Stream<Item = Result<InAppMessage, Status>> + Send>>;
 
 #[async_trait]
 impl Notification for NotificationService {
     type SendStream = ResponseStream;
+    type SubscribeStream = SubscribeStream;
 
     async fn send(
         &self,
@@ -37,6 +46,14 @@ impl Notification for NotificationService {
         let stream = request.into_inner();
         self.send(stream).await
     }
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> ServiceResult<SubscribeStream> {
+        let stream = self.subscribe(request.into_inner()).await?.into_inner();
+        Ok(Response::new(Box::pin(stream)))
+    }
 }
 
 #[cfg(feature = "test_utils")]
@@ -57,6 +74,7 @@ mod test_utils {
                 recipients: vec![SafeEmail().fake()],
                 subject: "Test Subject".to_string(),
                 body: "Test Body".to_string(),
+                html_body: "<p>Test Body</p>".to_string(),
             }
         }
     }
@@ -15,9 +15,12 @@ pub struct EmailMessage {
     /// recipients of the email
     #[prost(string, repeated, tag = "4")]
     pub recipients: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
-    /// body of the email
+    /// plain text body of the email
     #[prost(string, tag = "5")]
     pub body: ::prost::alloc::string::String,
+    /// html body of the email, empty for plain text only emails
+    #[prost(string, tag = "6")]
+    pub html_body: ::prost::alloc::string::String,
 }
 /// sms message to be sent
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -76,7 +79,7 @@ pub mod send_request {
         InApp(super::InAppMessage),
     }
 }
-/// response to send a message
+/// response to send a message, every message gets one whether it was delivered or not
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SendResponse {
@@ -86,6 +89,67 @@ pub struct SendResponse {
     /// timestamp of the message
     #[prost(message, optional, tag = "2")]
     pub timestamp: ::core::option::Option<::prost_types::Timestamp>,
+    /// id the provider assigned to the message, empty if unknown
+    #[prost(string, tag = "3")]
+    pub provider_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "DeliveryStatus", tag = "4")]
+    pub status: i32,
+    /// why the message was not delivered, empty if it was
+    #[prost(string, tag = "5")]
+    pub reason: ::prost::alloc::string::String,
+    /// recipients to send a retryable message again to, empty for all of them. The others
+    /// either got the message or will never get it.
+    #[prost(string, repeated, tag = "6")]
+    pub retry_recipients: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// request to receive the in-app messages of a device
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribeRequest {
+    /// id of the device, a new subscription of the device replaces the previous one
+    #[prost(string, tag = "1")]
+    pub device_id: ::prost::alloc::string::String,
+}
+/// what became of a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum DeliveryStatus {
+    Unspecified = 0,
+    /// the provider accepted the message
+    Delivered = 1,
+    /// the message may get through if it is sent again later, e.g. the provider is down
+    Retryable = 2,
+    /// the message will never get through, e.g. the recipient is invalid
+    Permanent = 3,
+    /// the message is kept in memory until the device subscribes, it is lost if the device
+    /// does not subscribe in time or the service restarts
+    Queued = 4,
+}
+impl DeliveryStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Unspecified => "DELIVERY_STATUS_UNSPECIFIED",
+            DeliveryStatus::Delivered => "DELIVERY_STATUS_DELIVERED",
+            DeliveryStatus::Retryable => "DELIVERY_STATUS_RETRYABLE",
+            DeliveryStatus::Permanent => "DELIVERY_STATUS_PERMANENT",
+            DeliveryStatus::Queued => "DELIVERY_STATUS_QUEUED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "DELIVERY_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "DELIVERY_STATUS_DELIVERED" => Some(Self::Delivered),
+            "DELIVERY_STATUS_RETRYABLE" => Some(Self::Retryable),
+            "DELIVERY_STATUS_PERMANENT" => Some(Self::Permanent),
+            "DELIVERY_STATUS_QUEUED" => Some(Self::Queued),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod notification_client {
@@ -191,6 +255,30 @@ pub mod notification_client {
                 .insert(GrpcMethod::new("notification.Notification", "Send"));
             self.inner.streaming(req, path, codec).await
         }
+        /// receive the in-app messages of the device, the ones sent while it was offline first.
+        /// Fails with FAILED_PRECONDITION unless the device backend delivers the in-app messages.
+        /// Anyone may subscribe to any device id, so it must sit behind a proxy authenticating the
+        /// device and checking the id it asks for.
+        pub async fn subscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SubscribeRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::InAppMessage>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/notification.Notification/Subscribe");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("notification.Notification", "Subscribe"));
+            self.inner.server_streaming(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -209,6 +297,19 @@ pub mod notification_server {
             &self,
             request: tonic::Request<tonic::Streaming<super::SendRequest>>,
         ) -> std::result::Result<tonic::Response<Self::SendStream>, tonic::Status>;
+        /// Server streaming response type for the Subscribe method.
+        type SubscribeStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::InAppMessage, tonic::Status>,
+            > + Send
+            + 'static;
+        /// receive the in-app messages of the device, the ones sent while it was offline first.
+        /// Fails with FAILED_PRECONDITION unless the device backend delivers the in-app messages.
+        /// Anyone may subscribe to any device id, so it must sit behind a proxy authenticating the
+        /// device and checking the id it asks for.
+        async fn subscribe(
+            &self,
+            request: tonic::Request<super::SubscribeRequest>,
+        ) -> std::result::Result<tonic::Response<Self::SubscribeStream>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct NotificationServer<T: Notification> {
@@ -327,6 +428,51 @@ pub mod notification_server {
                     };
                     Box::pin(fut)
                 }
+                "/notification.Notification/Subscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSvc<T: Notification>(pub Arc<T>);
+                    impl<T: Notification>
+                        tonic::server::ServerStreamingService<super::SubscribeRequest>
+                        for SubscribeSvc<T>
+                    {
+                        type Response = super::InAppMessage;
+                        type ResponseStream = T::SubscribeStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SubscribeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Notification>::subscribe(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
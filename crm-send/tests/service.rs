@@ -3,18 +3,24 @@ use std::{net::SocketAddr, time::Duration};
 use anyhow::Result;
 use crm_send::{
     pb::{
-        notification_client::NotificationClient, EmailMessage, InAppMessage, SendRequest,
-        SmsMessage,
+        notification_client::NotificationClient, DeliveryStatus, EmailMessage, InAppMessage,
+        SendRequest, SmsMessage, SubscribeRequest,
     },
-    AppConfig, NotificationService,
+    AppConfig, DeviceConfig, EmailConfig, InAppConfig, NotificationService, SmtpConfig, SmtpTls,
 };
 use futures::StreamExt;
-use tokio::time::sleep;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::mpsc,
+    time::timeout,
+};
+use tokio_stream::wrappers::TcpListenerStream;
 use tonic::{transport::Server, Request};
 
 #[tokio::test]
 async fn send_should_work() -> Result<()> {
-    let addr = start_server().await?;
+    let addr = start_server(AppConfig::load()?).await?;
     let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
 
     let stream = tokio_stream::iter(vec![
@@ -38,16 +44,141 @@ async fn send_should_work() -> Result<()> {
     Ok(())
 }
 
-async fn start_server() -> Result<SocketAddr> {
-    // Start the server
-    let conf = AppConfig::load()?;
-    let addr = SocketAddr::from(([127, 0, 0, 1], conf.server.port));
+#[tokio::test]
+async fn send_email_over_smtp_should_work() -> Result<()> {
+    let (smtp_addr, mut mails) = start_smtp_sink().await?;
+    let mut conf = AppConfig::load()?;
+    conf.email = EmailConfig::Smtp(SmtpConfig {
+        host: smtp_addr.ip().to_string(),
+        port: Some(smtp_addr.port()),
+        tls: SmtpTls::None,
+        username: None,
+        password: None,
+        pool_size: 1,
+        timeout: 5,
+    });
+    let addr = start_server(conf).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let email = EmailMessage::fake();
+    let stream = tokio_stream::iter(vec![SendRequest {
+        msg: Some(email.clone().into()),
+    }]);
+    let response = client.send(Request::new(stream)).await?.into_inner();
+    let ret: Vec<_> = response.collect().await;
+    assert_eq!(ret.len(), 1);
+    let res = ret[0].as_ref().unwrap();
+    assert_eq!(res.message_id, email.message_id);
+    assert_eq!(res.status(), DeliveryStatus::Delivered);
+
+    let mail = timeout(Duration::from_secs(5), mails.recv())
+        .await?
+        .unwrap();
+    assert!(mail.contains(&format!("Subject: {}", email.subject)));
+    assert!(mail.contains(&email.html_body));
+    Ok(())
+}
+
+#[tokio::test]
+async fn subscribe_should_receive_in_app_messages() -> Result<()> {
+    let mut conf = AppConfig::load()?;
+    conf.in_app = InAppConfig::Device(DeviceConfig {
+        max_pending: 10,
+        max_devices: 10,
+        pending_ttl: 60,
+    });
+    let addr = start_server(conf).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    // sent while the device is offline
+    let offline = InAppMessage::fake();
+    let stream = tokio_stream::iter(vec![SendRequest {
+        msg: Some(offline.clone().into()),
+    }]);
+    let ret: Vec<_> = client.send(stream).await?.into_inner().collect().await;
+    assert_eq!(ret[0].as_ref().unwrap().status(), DeliveryStatus::Queued);
+
+    let req = SubscribeRequest {
+        device_id: offline.device_id.clone(),
+    };
+    let mut messages = client.subscribe(req).await?.into_inner();
+    let msg = timeout(Duration::from_secs(5), messages.next())
+        .await?
+        .unwrap()?;
+    assert_eq!(msg, offline);
+
+    let live = InAppMessage {
+        message_id: "live".to_string(),
+        ..offline
+    };
+    let stream = tokio_stream::iter(vec![SendRequest {
+        msg: Some(live.clone().into()),
+    }]);
+    let ret: Vec<_> = client.send(stream).await?.into_inner().collect().await;
+    assert_eq!(ret[0].as_ref().unwrap().status(), DeliveryStatus::Delivered);
+    let msg = timeout(Duration::from_secs(5), messages.next())
+        .await?
+        .unwrap()?;
+    assert_eq!(msg, live);
+    Ok(())
+}
+
+async fn start_server(conf: AppConfig) -> Result<SocketAddr> {
+    // the port of the config may be taken by another test
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
 
     let svc = NotificationService::new(conf).into_server();
     tokio::spawn(async move {
-        let _ = Server::builder().add_service(svc).serve(addr).await;
+        let _ = Server::builder()
+            .add_service(svc)
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await;
     });
 
-    sleep(Duration::from_micros(1)).await;
     Ok(addr)
 }
+
+/// a minimal SMTP server accepting every email, the data of each email is sent to the receiver
+async fn start_smtp_sink() -> Result<(SocketAddr, mpsc::Receiver<String>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                writer.write_all(b"220 localhost ESMTP\r\n").await?;
+                let mut data: Option<String> = None;
+                while let Some(line) = lines.next_line().await? {
+                    let reply: &[u8] = match data.as_mut() {
+                        Some(mail) if line == "." => {
+                            tx.send(std::mem::take(mail)).await.ok();
+                            data = None;
+                            b"250 OK\r\n"
+                        }
+                        Some(mail) => {
+                            mail.push_str(&line);
+                            mail.push('\n');
+                            continue;
+                        }
+                        None if line.starts_with("DATA") => {
+                            data = Some(String::new());
+                            b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                        }
+                        None if line.starts_with("QUIT") => {
+                            writer.write_all(b"221 Bye\r\n").await?;
+                            break;
+                        }
+                        None => b"250 OK\r\n",
+                    };
+                    writer.write_all(reply).await?;
+                }
+                Ok::<_, std::io::Error>(())
+            });
+        }
+    });
+    Ok((addr, rx))
+}
@@ -7,7 +7,16 @@ fn main() -> Result<()> {
     let builder = tonic_build::configure();
     builder
         .out_dir("src/pb")
-        .with_derive_builder(&["WelcomeRequest", "RecallRequest", "RemindRequest"], None)
+        .extern_path(".metadata", "::crm_metadata::pb")
+        .with_derive_builder(
+            &[
+                "WelcomeRequest",
+                "RecallRequest",
+                "RemindRequest",
+                "NewReleaseRequest",
+            ],
+            None,
+        )
         .with_field_attributes(
             &["WelcomeRequest.content_ids"],
             &[r#"#[builder(setter(each(name="content_id", into)))]"#],
@@ -1,62 +1,63 @@
 pub mod auth;
 
 use crate::{
+    config::MissingContent,
     pb::{
-        RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest,
-        WelcomeResponse,
+        NewReleaseRequest, NewReleaseResponse, RecallRequest, RecallResponse, RemindRequest,
+        RemindResponse, WelcomeRequest, WelcomeResponse,
     },
     CrmService,
 };
 use chrono::{Duration, Utc};
-use crm_metadata::pb::{metadata_client::MetadataClient, Content, MaterializeRequest};
-use crm_send::pb::SendRequest;
-use futures::StreamExt;
-use std::sync::Arc;
+use crm_metadata::{
+    abi::{Tpl, BUILTIN_CONTENT_FIELDS},
+    pb::{
+        metadata_client::MetadataClient, BatchGetRequest, Content, GetActiveTemplateRequest,
+        ListContentsRequest, Template, TemplateChannel, TrendingRequest,
+    },
+};
+use crm_send::pb::{DeliveryStatus, SendRequest};
+use futures::{future::ready, StreamExt};
+use prost_types::FieldMask;
+use std::{collections::HashMap, future::Future, sync::Arc};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{transport::Channel, Response, Status};
+use tonic::{transport::Channel, Code, Response, Status, Streaming};
 use tracing::warn;
-use user_stat::pb::QueryRequest;
+use user_stat::pb::{Channel as NotifyChannel, QueryRequest, QueryRequestBuilder, User};
+
+/// custom attribute of the user region, e.g. CN
+const REGION_ATTRIBUTE: &str = "country";
+const CHANNEL_SIZE: usize = 1024;
 
 impl CrmService {
     pub async fn welcome(&self, req: WelcomeRequest) -> Result<Response<WelcomeResponse>, Status> {
         let request_id = req.id;
         let d1 = Utc::now() - Duration::days(req.interval as _);
         let d2 = d1 + Duration::days(1);
-        let query = QueryRequest::new_with_dt("created_at", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
-
-        let contents = get_contents_by_id(self.metadata.clone(), &req.content_ids).await?;
+        let mut query = QueryRequest::new_with_dt("created_at", d1, d2);
+        query.set_channel(NotifyChannel::Email);
+        let users = self.user_stats.clone().query(query).await?.into_inner();
 
-        let (tx, rx) = mpsc::channel(1024);
+        let contents = get_contents_by_id(
+            self.metadata.clone(),
+            &req.content_ids,
+            "",
+            &[],
+            self.config.server.missing_content,
+        )
+        .await?;
 
-        let sender = self.config.server.sender_email.clone();
-        tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new("Welcome".to_string(), sender, &[user.email], &contents);
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
+        let templates =
+            Templates::new(self.metadata.clone(), &req.template, "welcome", "Welcome").await?;
+        self.send_to_users(users, templates, move |user, _| {
+            let contents = contents_for(user, &contents);
+            if contents.is_empty() {
+                warn!("No content is available to {}", user.email);
             }
-        });
-        let reqs = ReceiverStream::new(rx);
-
-        // NOTE: this is an alternative solution
-        // let sender = self.config.server.sender_email.clone();
-        // let reqs = res.filter_map(move |v| {
-        //     let sender: String = sender.clone();
-        //     let contents = contents.clone();
-        //     async move {
-        //         let v = v.ok()?;
-        //         Some(gen_send_req("Welcome".to_string(), sender, v, &contents))
-        //     }
-        // });
-
-        self.notification.clone().send(reqs).await?;
+            ready((!contents.is_empty()).then_some(contents))
+        })
+        .await?;
 
         Ok(Response::new(WelcomeResponse { id: request_id }))
     }
@@ -65,29 +66,41 @@ impl CrmService {
         let request_id = req.id;
         let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
         let d2 = Utc::now();
-        let query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
-
-        let contents = get_contents_by_id(self.metadata.clone(), &req.content_ids).await?;
-
-        let (tx, rx) = mpsc::channel(1024);
+        let mut query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        query.set_channel(NotifyChannel::Email);
+        let users = self.user_stats.clone().query(query).await?.into_inner();
 
-        let sender = self.config.server.sender_email.clone();
-        tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                let contents = contents.clone();
-                let sender = sender.clone();
-                let tx = tx.clone();
-
-                let req = SendRequest::new("Recall".to_string(), sender, &[user.email], &contents);
-                if let Err(e) = tx.send(req).await {
-                    warn!("Failed to send message: {:?}", e);
-                }
+        let contents = match req.contents {
+            Some(query) => {
+                let query = ListContentsRequest {
+                    available_only: true,
+                    ..query
+                };
+                list_contents(self.metadata.clone(), query).await?
             }
-        });
-        let reqs = ReceiverStream::new(rx);
+            None if req.content_ids.is_empty() => trending(self.metadata.clone()).await?,
+            None => {
+                get_contents_by_id(
+                    self.metadata.clone(),
+                    &req.content_ids,
+                    "",
+                    &[],
+                    self.config.server.missing_content,
+                )
+                .await?
+            }
+        };
 
-        self.notification.clone().send(reqs).await?;
+        let templates =
+            Templates::new(self.metadata.clone(), &req.template, "recall", "Recall").await?;
+        self.send_to_users(users, templates, move |user, _| {
+            let contents = contents_for(user, &contents);
+            if contents.is_empty() {
+                warn!("No content is available to {}", user.email);
+            }
+            ready((!contents.is_empty()).then_some(contents))
+        })
+        .await?;
 
         Ok(Response::new(RecallResponse { id: request_id }))
     }
@@ -96,63 +109,354 @@ impl CrmService {
         let request_id = req.id;
         let d1 = Utc::now() - Duration::days(req.last_visit_interval as _);
         let d2 = Utc::now();
-        let query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
-        let mut res_user_stats = self.user_stats.clone().query(query).await?.into_inner();
-        let (tx, rx) = mpsc::channel(1024);
+        let mut query = QueryRequest::new_with_dt("last_visited_at", d1, d2);
+        query.set_channel(NotifyChannel::Email);
+        let users = self.user_stats.clone().query(query).await?.into_inner();
+        let templates =
+            Templates::new(self.metadata.clone(), &req.template, "remind", "Remind").await?;
 
         let metadata = self.metadata.clone();
+        let missing_content = self.config.server.missing_content;
+        self.send_to_users(users, templates, move |user, fields| {
+            let ids = user
+                .contents
+                .get("started_but_not_finished")
+                .map(|contents| contents.ids.clone());
+            let region = user_region(user).to_string();
+            let metadata = metadata.clone();
+            async move {
+                // fetched for every user, only the fields the template renders
+                let contents =
+                    get_contents_by_id(metadata, &ids?, &region, fields, missing_content).await;
+                match contents {
+                    Ok(contents) => Some(contents.to_vec()),
+                    Err(e) => {
+                        warn!("Failed to materialize contents: {:?}", e);
+                        None
+                    }
+                }
+            }
+        })
+        .await?;
+
+        Ok(Response::new(RemindResponse { id: request_id }))
+    }
+
+    pub async fn new_release(
+        &self,
+        req: NewReleaseRequest,
+    ) -> Result<Response<NewReleaseResponse>, Status> {
+        let request_id = req.id;
+        // the release must exist and be available, its regions are checked per follower
+        let contents = get_contents_by_id(
+            self.metadata.clone(),
+            &[req.content_id],
+            "",
+            &[],
+            MissingContent::Abort,
+        )
+        .await?;
+
+        let publisher_ids: Vec<u32> = contents[0].publishers.iter().map(|p| p.id).collect();
+        if publisher_ids.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Content {} has no publisher",
+                req.content_id
+            )));
+        }
+        let query = QueryRequestBuilder::default()
+            .followed_publisher_ids(publisher_ids)
+            .channel(NotifyChannel::Email as i32)
+            .build()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let users = self.user_stats.clone().query(query).await?.into_inner();
+
+        let templates = Templates::new(
+            self.metadata.clone(),
+            &req.template,
+            "new_release",
+            "New release",
+        )
+        .await?;
+        self.send_to_users(users, templates, move |user, _| {
+            let contents = contents_for(user, &contents);
+            ready((!contents.is_empty()).then_some(contents))
+        })
+        .await?;
+
+        Ok(Response::new(NewReleaseResponse { id: request_id }))
+    }
+
+    /// render the template for every user with the contents picked for the user, and send
+    /// all the messages in one stream. The contents are picked with the fields the template
+    /// of the user renders. Users without contents to send are skipped
+    async fn send_to_users<F, Fut>(
+        &self,
+        mut users: Streaming<User>,
+        mut templates: Templates,
+        mut contents_of: F,
+    ) -> Result<(), Status>
+    where
+        F: FnMut(&User, &'static [&'static str]) -> Fut + Send + 'static,
+        Fut: Future<Output = Option<Vec<Content>>> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
         let sender = self.config.server.sender_email.clone();
         tokio::spawn(async move {
-            while let Some(Ok(user)) = res_user_stats.next().await {
-                if let Some(started_but_not_finished_contents) =
-                    user.contents.get("started_but_not_finished")
-                {
-                    match get_contents_by_id(
-                        metadata.clone(),
-                        &started_but_not_finished_contents.ids,
-                    )
-                    .await
-                    {
-                        Ok(contents) => {
-                            let req = SendRequest::new(
-                                "Remind".to_string(),
-                                sender.clone(),
-                                &[user.email],
-                                &contents,
-                            );
-                            if let Err(e) = tx.send(req).await {
-                                warn!("Failed to send message: {:?}", e);
-                            }
-                        }
-                        Err(e) => {
-                            warn!("Failed to materialize contents: {:?}", e);
-                        }
+            while let Some(Ok(user)) = users.next().await {
+                let tpl = match templates.get(&user.locale).await {
+                    Ok(tpl) => tpl,
+                    Err(e) => {
+                        warn!("Failed to get template for {}: {:?}", user.locale, e);
+                        continue;
+                    }
+                };
+                let Some(contents) = contents_of(&user, tpl.fields).await else {
+                    continue;
+                };
+                let vars = user_vars(&user);
+                let req = match SendRequest::new(
+                    &tpl.tpl,
+                    sender.clone(),
+                    &[user.email],
+                    &contents,
+                    &vars,
+                ) {
+                    Ok(req) => req,
+                    Err(e) => {
+                        warn!("Failed to render message: {:?}", e);
+                        continue;
+                    }
+                };
+                // the notification service stopped reading
+                if let Err(e) = tx.send(req).await {
+                    warn!("Failed to send message: {:?}", e);
+                    break;
+                }
+            }
+        });
+
+        let mut responses = self
+            .notification
+            .clone()
+            .send(ReceiverStream::new(rx))
+            .await?
+            .into_inner();
+        // the campaign does not wait for the messages to be delivered
+        tokio::spawn(async move {
+            while let Some(res) = responses.next().await {
+                match res {
+                    Ok(res)
+                        if matches!(
+                            res.status(),
+                            DeliveryStatus::Delivered | DeliveryStatus::Queued
+                        ) => {}
+                    Ok(res) => warn!(
+                        "Message {} not delivered ({:?}): {}",
+                        res.message_id,
+                        res.status(),
+                        res.reason
+                    ),
+                    Err(e) => {
+                        warn!("Failed to read delivery results: {:?}", e);
+                        break;
                     }
                 }
             }
         });
-        let reqs = ReceiverStream::new(rx);
+        Ok(())
+    }
+}
 
-        self.notification.clone().send(reqs).await?;
+/// region of the user that contents must be licensed in, empty if unknown
+fn user_region(user: &User) -> &str {
+    user.attributes
+        .get(REGION_ATTRIBUTE)
+        .map_or("", |region| region.as_str())
+}
 
-        Ok(Response::new(RemindResponse { id: request_id }))
+/// the contents licensed in the region of the user
+fn contents_for(user: &User, contents: &[Content]) -> Vec<Content> {
+    let now = Utc::now();
+    let region = user_region(user);
+    contents
+        .iter()
+        .filter(|c| c.is_available(now, region))
+        .cloned()
+        .collect()
+}
+
+/// variables the templates can use for the user, custom attributes included
+fn user_vars(user: &User) -> HashMap<String, String> {
+    let mut vars = user.attributes.clone();
+    vars.insert("name".to_string(), user.name.clone());
+    vars.insert("email".to_string(), user.email.clone());
+    vars.insert("locale".to_string(), user.locale.clone());
+    vars
+}
+
+/// templates of a campaign by user locale, each locale is fetched once
+struct Templates {
+    metadata: MetadataClient<Channel>,
+    name: String,
+    default_subject: &'static str,
+    tpls: HashMap<String, LocaleTpl>,
+}
+
+/// template of a locale with the content fields it renders, all of them if empty
+#[derive(Clone)]
+struct LocaleTpl {
+    tpl: Arc<Tpl>,
+    fields: &'static [&'static str],
+}
+
+impl Templates {
+    /// fetch the template of the default locale up front so that a broken campaign fails early
+    async fn new(
+        metadata: MetadataClient<Channel>,
+        name: &str,
+        default_name: &str,
+        default_subject: &'static str,
+    ) -> Result<Self, Status> {
+        let name = if name.is_empty() { default_name } else { name };
+        let mut templates = Self {
+            metadata,
+            name: name.to_string(),
+            default_subject,
+            tpls: HashMap::new(),
+        };
+        templates.get("").await?;
+        Ok(templates)
+    }
+
+    async fn get(&mut self, locale: &str) -> Result<LocaleTpl, Status> {
+        if let Some(tpl) = self.tpls.get(locale) {
+            return Ok(tpl.clone());
+        }
+        let tpl = get_template(
+            self.metadata.clone(),
+            &self.name,
+            locale,
+            self.default_subject,
+        )
+        .await?;
+        self.tpls.insert(locale.to_string(), tpl.clone());
+        Ok(tpl)
     }
 }
 
+/// the published template of the name in the locale or its fallbacks, or the built-in one
+/// if it is never published
+/// the active template of the locale, or the built-in one if none is published. Only the
+/// built-in template is known to render a subset of the content fields.
+async fn get_template(
+    metadata: MetadataClient<Channel>,
+    name: &str,
+    locale: &str,
+    default_subject: &str,
+) -> Result<LocaleTpl, Status> {
+    let req = GetActiveTemplateRequest {
+        name: name.to_string(),
+        locale: locale.to_string(),
+    };
+    let (template, fields) = match metadata.clone().get_active_template(req).await {
+        Ok(template) => (template.into_inner(), &[][..]),
+        Err(status) if status.code() == Code::NotFound => {
+            warn!("Template {} is not published, use the built-in one", name);
+            (
+                Template::builtin(name, default_subject),
+                BUILTIN_CONTENT_FIELDS,
+            )
+        }
+        Err(status) => return Err(status),
+    };
+    if template.channel() != TemplateChannel::Email {
+        return Err(Status::failed_precondition(format!(
+            "Template {} is not an email template",
+            name
+        )));
+    }
+
+    let tpl = Tpl::new(&template)
+        .map_err(|e| Status::internal(format!("Invalid template {}: {}", name, e)))?;
+    Ok(LocaleTpl {
+        tpl: Arc::new(tpl),
+        fields,
+    })
+}
+
+/// the available contents of the ids in the given order, unavailable ones are always skipped.
+/// Only the given fields are fetched, all of them if empty.
 async fn get_contents_by_id(
     metadata: MetadataClient<Channel>,
     ids: &[u32],
+    region: &str,
+    fields: &[&str],
+    missing_content: MissingContent,
+) -> Result<Arc<Vec<Content>>, Status> {
+    let req = BatchGetRequest {
+        ids: ids.to_vec(),
+        available_only: true,
+        region: region.to_string(),
+        fields: Some(FieldMask {
+            paths: fields.iter().map(|f| f.to_string()).collect(),
+        }),
+    };
+    let ret = metadata.clone().batch_get(req).await?.into_inner();
+
+    if !ret.unavailable_ids.is_empty() {
+        warn!("Skip unavailable contents: {:?}", ret.unavailable_ids);
+    }
+    if !ret.missing_ids.is_empty() {
+        match missing_content {
+            MissingContent::Degrade => warn!("Skip missing contents: {:?}", ret.missing_ids),
+            MissingContent::Abort => {
+                return Err(Status::not_found(format!(
+                    "Contents not found: {:?}",
+                    ret.missing_ids
+                )))
+            }
+        }
+    }
+
+    // degrading to a campaign without any content makes no sense
+    if ret.contents.is_empty() && !ids.is_empty() {
+        return Err(Status::failed_precondition(
+            "None of the contents could be materialized",
+        ));
+    }
+    Ok(Arc::new(ret.contents))
+}
+
+async fn list_contents(
+    metadata: MetadataClient<Channel>,
+    query: ListContentsRequest,
 ) -> Result<Arc<Vec<Content>>, Status> {
     let contents = metadata
         .clone()
-        .materialize(MaterializeRequest::new_with_ids(ids))
+        .list_contents(query)
         .await?
-        .into_inner();
+        .into_inner()
+        .contents;
+    if contents.is_empty() {
+        return Err(Status::failed_precondition("No content matches the query"));
+    }
+    Ok(Arc::new(contents))
+}
 
-    let contents: Vec<Content> = contents
-        .filter_map(|v| async move { v.ok() })
-        .collect()
-        .await;
-    let contents = Arc::new(contents);
-    Ok(contents)
+async fn trending(metadata: MetadataClient<Channel>) -> Result<Arc<Vec<Content>>, Status> {
+    let contents: Vec<Content> = metadata
+        .clone()
+        .trending(TrendingRequest::default())
+        .await?
+        .into_inner()
+        .contents
+        .into_iter()
+        .filter_map(|c| c.content)
+        .filter(|c| c.is_available(Utc::now(), ""))
+        .collect();
+    if contents.is_empty() {
+        return Err(Status::failed_precondition("No trending content"));
+    }
+    Ok(Arc::new(contents))
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use crm::pb::{
-    crm_client::CrmClient, RecallRequestBuilder, RemindRequestBuilder, WelcomeRequestBuilder,
+    crm_client::CrmClient, NewReleaseRequestBuilder, RecallRequestBuilder, RemindRequestBuilder,
+    WelcomeRequestBuilder,
 };
 use tonic::{
     metadata::MetadataValue,
@@ -63,5 +64,16 @@ async fn main() -> Result<()> {
         .into_inner();
     println!("Response of remind: {:?}", resp_of_remind);
 
+    let req_of_new_release = NewReleaseRequestBuilder::default()
+        .id(Uuid::new_v4().to_string())
+        .content_id(1u32)
+        .build()?;
+
+    let resp_of_new_release = client
+        .new_release(Request::new(req_of_new_release))
+        .await?
+        .into_inner();
+    println!("Response of new release: {:?}", resp_of_new_release);
+
     Ok(())
 }
@@ -21,9 +21,22 @@ pub struct ServerConfig {
     pub metadata: String,
     pub user_stats: String,
     pub notification: String,
+    /// what to do when some of the campaign contents can't be materialized
+    #[serde(default)]
+    pub missing_content: MissingContent,
     pub tls: Option<TlsConfig>,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingContent {
+    /// fail the campaign
+    #[default]
+    Abort,
+    /// send the campaign with the contents that are available
+    Degrade,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TlsConfig {
     pub cert: String,
@@ -10,7 +10,8 @@ use crm_metadata::pb::metadata_client::MetadataClient;
 use crm_send::pb::notification_client::NotificationClient;
 use pb::{
     crm_server::{Crm, CrmServer},
-    RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest, WelcomeResponse,
+    NewReleaseRequest, NewReleaseResponse, RecallRequest, RecallResponse, RemindRequest,
+    RemindResponse, WelcomeRequest, WelcomeResponse,
 };
 use tonic::{
     async_trait, service::interceptor::InterceptedService, transport::Channel, Request, Response,
@@ -56,6 +57,15 @@ impl Crm for CrmService {
         info!("User: {:?}", user);
         self.remind(request.into_inner()).await
     }
+
+    async fn new_release(
+        &self,
+        request: Request<NewReleaseRequest>,
+    ) -> Result<Response<NewReleaseResponse>, Status> {
+        let user: &auth::User = request.extensions().get().unwrap();
+        info!("User: {:?}", user);
+        self.new_release(request.into_inner()).await
+    }
 }
 
 impl CrmService {
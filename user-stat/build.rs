@@ -7,6 +7,7 @@ fn main() -> Result<()> {
     let builder = tonic_build::configure();
     builder
         .out_dir("src/pb")
+        .extern_path(".metadata", "::crm_metadata::pb")
         .with_serde(
             &["User, IdContent"],
             true,
@@ -21,6 +22,9 @@ fn main() -> Result<()> {
                 "RawQueryRequest",
                 "TimeQuery",
                 "IdQuery",
+                "ConsentRequest",
+                "ContentQuery",
+                "FollowRequest",
             ],
             None,
         )
@@ -30,6 +34,9 @@ fn main() -> Result<()> {
                 "User.name",
                 "User.contents",
                 "RawQueryRequest.query",
+                "ConsentRequest.email",
+                "ConsentRequest.source",
+                "FollowRequest.email",
             ],
             &[r#"#[builder(setter(into))]"#],
         )
@@ -45,6 +52,18 @@ fn main() -> Result<()> {
             &["QueryRequest.ids"],
             &[r#"#[builder(setter(each(name="id", into)))]"#],
         )
+        .with_field_attributes(
+            &["QueryRequest.attributes"],
+            &[r#"#[builder(setter(each(name="attribute", into)))]"#],
+        )
+        .with_field_attributes(
+            &["QueryRequest.contents"],
+            &[r#"#[builder(setter(each(name="content", into)))]"#],
+        )
+        .with_field_attributes(
+            &["QueryRequest.followed_publisher_ids"],
+            &[r#"#[builder(setter(each(name="followed_publisher_id")))]"#],
+        )
         .compile(
             &[
                 "../protos/user-stats/messages.proto",
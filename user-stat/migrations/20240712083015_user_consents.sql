@@ -0,0 +1,11 @@
+-- Add migration script here
+alter table user_stats
+    add column email_consent boolean NOT NULL DEFAULT true,
+    add column email_consent_at timestamptz,
+    add column email_consent_source varchar(64),
+    add column sms_consent boolean NOT NULL DEFAULT true,
+    add column sms_consent_at timestamptz,
+    add column sms_consent_source varchar(64),
+    add column in_app_consent boolean NOT NULL DEFAULT true,
+    add column in_app_consent_at timestamptz,
+    add column in_app_consent_source varchar(64);
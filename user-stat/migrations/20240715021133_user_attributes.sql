@@ -0,0 +1,3 @@
+-- Add migration script here
+alter table user_stats add column attributes jsonb NOT NULL DEFAULT '{}';
+create index user_stats_attributes_idx on user_stats using GIN(attributes);
@@ -0,0 +1,11 @@
+-- Add migration script here
+create or replace function notify_user_stats_changed() returns trigger as $$
+begin
+    perform pg_notify('user_stats_changed', NEW.email);
+    return NEW;
+end;
+$$ language plpgsql;
+
+create trigger user_stats_changed
+    after insert or update on user_stats
+    for each row execute function notify_user_stats_changed();
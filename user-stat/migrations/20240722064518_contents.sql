@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TYPE content_type AS ENUM('unspecified', 'short', 'vlog', 'movie', 'ai_generated');
+create table contents(
+    id int NOT NULL PRIMARY KEY,
+    type content_type NOT NULL DEFAULT 'unspecified',
+    publisher_ids int[] NOT NULL DEFAULT '{}',
+    updated_at timestamptz DEFAULT CURRENT_TIMESTAMP
+);
+create index contents_type_idx on contents(type);
+create index contents_publisher_ids_idx on contents using GIN(publisher_ids);
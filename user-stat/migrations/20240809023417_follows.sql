@@ -0,0 +1,8 @@
+-- Add migration script here
+create table follows(
+    email varchar(128) NOT NULL REFERENCES user_stats(email) ON DELETE CASCADE,
+    publisher_id int NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (email, publisher_id)
+);
+create index follows_publisher_id_idx on follows(publisher_id);
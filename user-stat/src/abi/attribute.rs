@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+
+use crate::pb::{attribute_query::Op, AttributeQuery, NumberQuery};
+
+impl AttributeQuery {
+    pub fn equals(value: impl Into<String>) -> Self {
+        Self {
+            op: Some(Op::Equals(value.into())),
+        }
+    }
+
+    pub fn exists(exists: bool) -> Self {
+        Self {
+            op: Some(Op::Exists(exists)),
+        }
+    }
+
+    pub fn number(lower: Option<f64>, upper: Option<f64>) -> Self {
+        Self {
+            op: Some(Op::Number(NumberQuery { lower, upper })),
+        }
+    }
+}
+
+pub(super) fn attribute_query(key: &str, query: &AttributeQuery) -> Result<String> {
+    let name = quote(key);
+    let sql = match &query.op {
+        None => "TRUE".to_string(),
+        // containment is served by the GIN index
+        Some(Op::Equals(value)) => {
+            format!(
+                "attributes @> {}::jsonb",
+                quote(&json!({ key: value }).to_string())
+            )
+        }
+        Some(Op::Exists(true)) => format!("attributes ? {}", name),
+        Some(Op::Exists(false)) => format!("NOT attributes ? {}", name),
+        Some(Op::Number(NumberQuery { lower, upper })) => {
+            let value = format!("(attributes->>{})::numeric", name);
+            let mut conditions = Vec::new();
+            if let Some(lower) = lower {
+                conditions.push(format!("{} >= {}", value, number(*lower)?));
+            }
+            if let Some(upper) = upper {
+                conditions.push(format!("{} <= {}", value, number(*upper)?));
+            }
+            if conditions.is_empty() {
+                conditions.push("TRUE".to_string());
+            }
+            // only cast the attribute when it holds a number, otherwise the cast fails the query
+            format!(
+                "attributes ? {name} AND CASE WHEN jsonb_typeof(attributes->{name}) = 'number' THEN {} ELSE false END",
+                conditions.join(" AND ")
+            )
+        }
+    };
+    Ok(sql)
+}
+
+pub(super) fn to_attributes(attributes: Map<String, Value>) -> HashMap<String, String> {
+    attributes
+        .into_iter()
+        .map(|(k, v)| {
+            let v = match v {
+                Value::String(s) => s,
+                v => v.to_string(),
+            };
+            (k, v)
+        })
+        .collect()
+}
+
+fn number(v: f64) -> Result<f64> {
+    if !v.is_finite() {
+        bail!("Invalid number in attribute query: {}", v);
+    }
+    Ok(v)
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pb::QueryRequestBuilder, UserStatsService};
+
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn attribute_query_should_escape_quotes() -> Result<()> {
+        let sql = attribute_query("it's", &AttributeQuery::exists(true))?;
+        assert_eq!(sql, "attributes ? 'it''s'");
+
+        let sql = attribute_query("tier", &AttributeQuery::equals("o'neil"))?;
+        assert_eq!(sql, r#"attributes @> '{"tier":"o''neil"}'::jsonb"#);
+        Ok(())
+    }
+
+    #[test]
+    fn attribute_query_should_reject_nan() {
+        let ret = attribute_query("version", &AttributeQuery::number(Some(f64::NAN), None));
+        assert!(ret.is_err());
+    }
+
+    #[tokio::test]
+    async fn query_by_attributes_should_work() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        sqlx::query(
+            r#"UPDATE user_stats SET attributes = '{"tier": "pro", "country": "CN", "version": 12}'
+            WHERE email = 'ettie.yfmn9tqn@example.net'"#,
+        )
+        .execute(&service.pool)
+        .await?;
+        sqlx::query(
+            r#"UPDATE user_stats SET attributes = '{"tier": "free", "version": "beta"}'
+            WHERE email = 'jeanie.f44ky2at@example.net'"#,
+        )
+        .execute(&service.pool)
+        .await?;
+
+        let emails = query_emails(&service, "tier", AttributeQuery::equals("pro")).await?;
+        assert_eq!(emails, ["ettie.yfmn9tqn@example.net"]);
+
+        let emails = query_emails(&service, "tier", AttributeQuery::exists(true)).await?;
+        assert_eq!(emails.len(), 2);
+
+        let emails = query_emails(&service, "country", AttributeQuery::exists(false)).await?;
+        assert!(!emails.contains(&"ettie.yfmn9tqn@example.net".to_string()));
+        assert!(emails.contains(&"jeanie.f44ky2at@example.net".to_string()));
+
+        let query = AttributeQuery::number(Some(10.0), Some(12.0));
+        let emails = query_emails(&service, "version", query).await?;
+        assert_eq!(emails, ["ettie.yfmn9tqn@example.net"]);
+
+        let query = AttributeQuery::number(None, Some(11.5));
+        let emails = query_emails(&service, "version", query).await?;
+        assert!(emails.is_empty());
+
+        Ok(())
+    }
+
+    async fn query_emails(
+        service: &UserStatsService,
+        key: &str,
+        query: AttributeQuery,
+    ) -> Result<Vec<String>> {
+        let query = QueryRequestBuilder::default()
+            .attribute((key.to_string(), query))
+            .build()?;
+        let stream = service.query(query).await?.into_inner();
+        let mut emails: Vec<String> = stream.map(|user| user.unwrap().email).collect().await;
+        emails.sort();
+        Ok(emails)
+    }
+}
@@ -0,0 +1,145 @@
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{Channel, ConsentRequest, User},
+    ServiceResult, UserStatsService,
+};
+
+use super::UserModel;
+
+impl UserStatsService {
+    pub async fn opt_in(&self, req: ConsentRequest) -> ServiceResult<User> {
+        self.update_consent(req, true).await
+    }
+
+    pub async fn opt_out(&self, req: ConsentRequest) -> ServiceResult<User> {
+        self.update_consent(req, false).await
+    }
+
+    async fn update_consent(&self, req: ConsentRequest, granted: bool) -> ServiceResult<User> {
+        let Some(prefix) = req.channel().column_prefix() else {
+            return Err(Status::invalid_argument("Channel must be specified"));
+        };
+
+        let sql = format!(
+            "UPDATE user_stats SET {prefix}_consent = $1, {prefix}_consent_at = now(), {prefix}_consent_source = $2 WHERE email = $3 RETURNING *"
+        );
+        let source = (!req.source.is_empty()).then_some(&req.source);
+        let ret = sqlx::query_as::<_, UserModel>(&sql)
+            .bind(granted)
+            .bind(source)
+            .bind(&req.email)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        match ret {
+            Some(user) => Ok(Response::new(user.into_user())),
+            None => Err(Status::not_found(format!("User not found: {}", req.email))),
+        }
+    }
+}
+
+impl Channel {
+    /// prefix of the consent columns in user_stats, None for unspecified channel
+    pub fn column_prefix(&self) -> Option<&'static str> {
+        match self {
+            Channel::Unspecified => None,
+            Channel::Email => Some("email"),
+            Channel::Sms => Some("sms"),
+            Channel::InApp => Some("in_app"),
+        }
+    }
+}
+
+pub(super) fn consent_query(channel: Channel) -> String {
+    match channel.column_prefix() {
+        Some(prefix) => format!("{}_consent", prefix),
+        None => "email_consent AND sms_consent AND in_app_consent".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        pb::{ConsentRequestBuilder, QueryRequestBuilder},
+        test_utils::tq,
+    };
+
+    use super::*;
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    const EMAIL: &str = "ettie.yfmn9tqn@example.net";
+
+    #[tokio::test]
+    async fn opt_out_should_exclude_user_from_query() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let req = ConsentRequestBuilder::default()
+            .email(EMAIL)
+            .channel(Channel::Email as i32)
+            .source("unsubscribe_link")
+            .build()?;
+        let user = service.opt_out(req).await?.into_inner();
+        let consent = &user.consents["email"];
+        assert!(!consent.granted);
+        assert!(consent.updated_at.is_some());
+        assert_eq!(consent.source, "unsubscribe_link");
+        assert!(user.consents["sms"].granted);
+
+        let emails = query_emails(&service, Channel::Email, false).await?;
+        assert!(!emails.contains(&EMAIL.to_string()));
+
+        let emails = query_emails(&service, Channel::Sms, false).await?;
+        assert!(emails.contains(&EMAIL.to_string()));
+
+        let emails = query_emails(&service, Channel::Unspecified, false).await?;
+        assert!(!emails.contains(&EMAIL.to_string()));
+
+        let emails = query_emails(&service, Channel::Email, true).await?;
+        assert!(emails.contains(&EMAIL.to_string()));
+
+        let req = ConsentRequestBuilder::default()
+            .email(EMAIL)
+            .channel(Channel::Email as i32)
+            .build()?;
+        let user = service.opt_in(req).await?.into_inner();
+        assert!(user.consents["email"].granted);
+
+        let emails = query_emails(&service, Channel::Email, false).await?;
+        assert!(emails.contains(&EMAIL.to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn update_consent_should_reject_bad_request() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let req = ConsentRequestBuilder::default().email(EMAIL).build()?;
+        let err = service.opt_out(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let req = ConsentRequestBuilder::default()
+            .email("nobody@example.net")
+            .channel(Channel::Sms as i32)
+            .build()?;
+        let err = service.opt_out(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    async fn query_emails(
+        service: &UserStatsService,
+        channel: Channel,
+        include_opted_out: bool,
+    ) -> Result<Vec<String>> {
+        // the fixture user was created at 2024-03-09
+        let query = QueryRequestBuilder::default()
+            .timestamp(("created_at".to_string(), tq(Some(60), Some(58))))
+            .channel(channel as i32)
+            .include_opted_out(include_opted_out)
+            .build()?;
+        let stream = service.query(query).await?.into_inner();
+        let emails = stream.map(|user| user.unwrap().email).collect().await;
+        Ok(emails)
+    }
+}
@@ -0,0 +1,311 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use crm_metadata::pb::{
+    metadata_client::MetadataClient, Content, ContentType, ListContentsRequest,
+};
+use futures::{Stream, StreamExt};
+use sqlx::PgPool;
+use tonic::{
+    transport::{Channel, Endpoint},
+    Response, Status,
+};
+use tracing::{info, warn};
+
+use crate::{
+    pb::{ContentQuery, SyncContentsResponse},
+    ServiceResult, UserStatsService,
+};
+
+/// contents pulled from crm-metadata per request
+const SYNC_PAGE_SIZE: u32 = 100;
+/// columns of user_stats holding content ids, the only keys a content query may use
+const CONTENT_ID_COLUMNS: [&str; 4] = [
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+    "recent_watched",
+];
+
+impl UserStatsService {
+    pub async fn sync_contents(
+        &self,
+        mut stream: impl Stream<Item = Result<Content, Status>> + Send + Unpin,
+    ) -> ServiceResult<SyncContentsResponse> {
+        let mut count = 0;
+        while let Some(content) = stream.next().await {
+            upsert_content(&self.pool, &content?).await?;
+            count += 1;
+        }
+
+        Ok(Response::new(SyncContentsResponse { count }))
+    }
+
+    /// pull all the contents from crm-metadata, the ones it no longer has are removed
+    pub async fn pull_contents(&self, client: &mut MetadataClient<Channel>) -> Result<u32> {
+        // contents upserted from now on are newer, in postgres time
+        let started: DateTime<Utc> = sqlx::query_scalar("SELECT now()")
+            .fetch_one(&self.pool)
+            .await?;
+        let mut count = 0;
+        let mut cursor = String::new();
+        loop {
+            let req = ListContentsRequest {
+                page_size: SYNC_PAGE_SIZE,
+                cursor,
+                ..Default::default()
+            };
+            let res = client.list_contents(req).await?.into_inner();
+            for content in &res.contents {
+                upsert_content(&self.pool, content).await?;
+                count += 1;
+            }
+            if res.next_cursor.is_empty() {
+                break;
+            }
+            cursor = res.next_cursor;
+        }
+
+        sqlx::query("DELETE FROM contents WHERE updated_at < $1")
+            .bind(started)
+            .execute(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    /// pull the contents from crm-metadata every interval until the service is dropped,
+    /// the connection is made on the first pull
+    pub(crate) fn spawn_content_sync(&self, url: &str, interval: Duration) -> Result<()> {
+        let channel = Endpoint::from_shared(url.to_string())?.connect_lazy();
+        let mut client = MetadataClient::new(channel);
+        let inner = Arc::downgrade(&self.inner);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                let svc = UserStatsService { inner };
+                match svc.pull_contents(&mut client).await {
+                    Ok(count) => info!("Synced {} contents from crm-metadata", count),
+                    Err(e) => warn!("Failed to sync contents from crm-metadata: {}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+async fn upsert_content(pool: &PgPool, content: &Content) -> Result<(), Status> {
+    let publisher_ids: Vec<i32> = content.publishers.iter().map(|p| p.id as i32).collect();
+    sqlx::query(
+        r#"INSERT INTO contents (id, type, publisher_ids, updated_at)
+        VALUES ($1, $2::content_type, $3, now())
+        ON CONFLICT (id) DO UPDATE
+        SET type = EXCLUDED.type, publisher_ids = EXCLUDED.publisher_ids, updated_at = now()"#,
+    )
+    .bind(content.id as i32)
+    .bind(type_name(content.r#type).map_err(|e| Status::invalid_argument(e.to_string()))?)
+    .bind(publisher_ids)
+    .execute(pool)
+    .await
+    .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+    Ok(())
+}
+
+/// match users whose content column overlaps with the contents satisfying the query,
+/// the content ids are resolved once by postgres before the users are scanned
+pub(super) fn content_query(name: &str, query: &ContentQuery) -> Result<String> {
+    if !CONTENT_ID_COLUMNS.contains(&name) {
+        bail!("Unknown content column: {}", name);
+    }
+    let mut conditions = Vec::new();
+    if !query.types.is_empty() {
+        let types = query
+            .types
+            .iter()
+            .map(|t| type_name(*t).map(|t| format!("'{}'", t)))
+            .collect::<Result<Vec<_>>>()?;
+        conditions.push(format!("type IN ({})", types.join(", ")));
+    }
+    if !query.publisher_ids.is_empty() {
+        conditions.push(format!(
+            "publisher_ids && array{:?}::int[]",
+            query.publisher_ids
+        ));
+    }
+    if conditions.is_empty() {
+        conditions.push("TRUE".to_string());
+    }
+
+    Ok(format!(
+        "{} && ARRAY(SELECT id FROM contents WHERE {})",
+        name,
+        conditions.join(" AND ")
+    ))
+}
+
+/// label of the content_type enum in postgres, e.g. CONTENT_TYPE_MOVIE is movie
+fn type_name(value: i32) -> Result<String> {
+    let t = ContentType::try_from(value).map_err(|_| anyhow!("Invalid content type: {}", value))?;
+    let name = t.as_str_name().trim_start_matches("CONTENT_TYPE_");
+    Ok(name.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, net::SocketAddr};
+
+    use crm_metadata::{
+        pb::{DeleteContentRequest, Publisher},
+        MetadataService,
+    };
+    use sqlx_db_tester::TestPg;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use crate::pb::{ContentQueryBuilder, QueryRequestBuilder};
+
+    use super::*;
+
+    #[test]
+    fn content_query_should_work() -> Result<()> {
+        let query = ContentQueryBuilder::default()
+            .types(vec![
+                ContentType::Movie as i32,
+                ContentType::AiGenerated as i32,
+            ])
+            .publisher_ids(vec![1, 2])
+            .build()?;
+        let sql = content_query("recent_watched", &query)?;
+        assert_eq!(
+            sql,
+            "recent_watched && ARRAY(SELECT id FROM contents WHERE type IN ('movie', 'ai_generated') AND publisher_ids && array[1, 2]::int[])"
+        );
+
+        let query = ContentQueryBuilder::default().types(vec![42]).build()?;
+        assert!(content_query("finished", &query).is_err());
+
+        // the key ends up in the sql
+        let query = ContentQueryBuilder::default().build()?;
+        assert!(content_query("true) OR (1 = 1", &query).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn query_by_contents_should_work() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        // 176911 is only watched by ettie, 499355 is only finished by ettie
+        let contents = vec![
+            content(176911, ContentType::Movie, &[7]),
+            content(499355, ContentType::Vlog, &[8]),
+        ];
+        let stream = tokio_stream::iter(contents.into_iter().map(Ok));
+        let ret = service.sync_contents(stream).await?.into_inner();
+        assert_eq!(ret.count, 2);
+
+        let emails = query_emails(&service, "recent_watched", &[ContentType::Movie], &[]).await?;
+        assert_eq!(emails, ["ettie.yfmn9tqn@example.net"]);
+
+        let emails = query_emails(&service, "finished", &[], &[8]).await?;
+        assert_eq!(emails, ["ettie.yfmn9tqn@example.net"]);
+
+        let emails = query_emails(&service, "finished", &[ContentType::Movie], &[8]).await?;
+        assert!(emails.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pull_contents_should_mirror_crm_metadata() -> Result<()> {
+        let (_mtdb, metadata, addr) = start_metadata().await?;
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let mut client = MetadataClient::connect(format!("http://{addr}")).await?;
+
+        assert_eq!(service.pull_contents(&mut client).await?, 5);
+        assert_eq!(
+            synced_contents(&service).await?,
+            [
+                (1, "vlog".to_string(), vec![10001, 10002]),
+                (2, "movie".to_string(), vec![10003]),
+                (3, "short".to_string(), vec![10004]),
+                (4, "ai_generated".to_string(), vec![10002]),
+                (5, "movie".to_string(), vec![10003, 10001]),
+            ]
+        );
+
+        metadata
+            .delete_content(DeleteContentRequest { id: 3 })
+            .await?;
+        assert_eq!(service.pull_contents(&mut client).await?, 4);
+        let ids: Vec<_> = synced_contents(&service)
+            .await?
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        assert_eq!(ids, [1, 2, 4, 5]);
+        Ok(())
+    }
+
+    async fn start_metadata() -> Result<(TestPg, MetadataService, SocketAddr)> {
+        // the config of crm-metadata is not in the working directory of these tests
+        env::set_var(
+            "METADATA_CONFIG",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../crm-metadata/metadata.yml"),
+        );
+        let (tdb, metadata) = MetadataService::new_for_test().await?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let svc = metadata.clone().into_server();
+        tokio::spawn(async move {
+            let _ = Server::builder()
+                .add_service(svc)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await;
+        });
+        Ok((tdb, metadata, addr))
+    }
+
+    async fn synced_contents(service: &UserStatsService) -> Result<Vec<(i32, String, Vec<i32>)>> {
+        let rows = sqlx::query_as("SELECT id, type::text, publisher_ids FROM contents ORDER BY id")
+            .fetch_all(&service.pool)
+            .await?;
+        Ok(rows)
+    }
+
+    fn content(id: u32, t: ContentType, publishers: &[u32]) -> Content {
+        Content {
+            id,
+            r#type: t as i32,
+            publishers: publishers
+                .iter()
+                .map(|id| Publisher {
+                    id: *id,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    async fn query_emails(
+        service: &UserStatsService,
+        name: &str,
+        types: &[ContentType],
+        publisher_ids: &[u32],
+    ) -> Result<Vec<String>> {
+        let content = ContentQueryBuilder::default()
+            .types(types.iter().map(|t| *t as i32).collect::<Vec<_>>())
+            .publisher_ids(publisher_ids.to_vec())
+            .build()?;
+        let query = QueryRequestBuilder::default()
+            .content((name.to_string(), content))
+            .build()?;
+        let stream = service.query(query).await?.into_inner();
+        let emails = stream.map(|user| user.unwrap().email).collect().await;
+        Ok(emails)
+    }
+}
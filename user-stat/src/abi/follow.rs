@@ -0,0 +1,152 @@
+use tonic::{Response, Status};
+
+use crate::{
+    pb::{FollowRequest, FollowResponse},
+    ServiceResult, UserStatsService,
+};
+
+impl UserStatsService {
+    pub async fn follow(&self, req: FollowRequest) -> ServiceResult<FollowResponse> {
+        validate(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        // following a publisher twice is a no-op
+        let ret = sqlx::query(
+            r#"INSERT INTO follows (email, publisher_id)
+            SELECT email, $2 FROM user_stats WHERE email = $1
+            ON CONFLICT (email, publisher_id) DO NOTHING"#,
+        )
+        .bind(&req.email)
+        .bind(req.publisher_id as i32)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+        if ret.rows_affected() == 0 && !self.user_exists(&req.email).await? {
+            return Err(Status::not_found(format!("User not found: {}", req.email)));
+        }
+
+        self.followed_publishers(&req.email).await
+    }
+
+    pub async fn unfollow(&self, req: FollowRequest) -> ServiceResult<FollowResponse> {
+        validate(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let ret = sqlx::query("DELETE FROM follows WHERE email = $1 AND publisher_id = $2")
+            .bind(&req.email)
+            .bind(req.publisher_id as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+        if ret.rows_affected() == 0 && !self.user_exists(&req.email).await? {
+            return Err(Status::not_found(format!("User not found: {}", req.email)));
+        }
+
+        self.followed_publishers(&req.email).await
+    }
+
+    async fn followed_publishers(&self, email: &str) -> ServiceResult<FollowResponse> {
+        let ids: Vec<i32> = sqlx::query_scalar(
+            "SELECT publisher_id FROM follows WHERE email = $1 ORDER BY publisher_id",
+        )
+        .bind(email)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        Ok(Response::new(FollowResponse {
+            publisher_ids: ids.into_iter().map(|id| id as u32).collect(),
+        }))
+    }
+
+    async fn user_exists(&self, email: &str) -> Result<bool, Status> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM user_stats WHERE email = $1)")
+            .bind(email)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))
+    }
+}
+
+fn validate(req: &FollowRequest) -> anyhow::Result<()> {
+    if req.email.is_empty() || req.publisher_id == 0 {
+        anyhow::bail!("Email and publisher id must be specified");
+    }
+    Ok(())
+}
+
+/// match users following any of the publishers
+pub(super) fn follow_query(publisher_ids: &[u32]) -> String {
+    format!(
+        "email IN (SELECT email FROM follows WHERE publisher_id = ANY(array{:?}::int[]))",
+        publisher_ids
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pb::{FollowRequestBuilder, QueryRequestBuilder};
+
+    use super::*;
+    use anyhow::Result;
+    use futures::StreamExt;
+
+    const EMAIL: &str = "ettie.yfmn9tqn@example.net";
+    const OTHER_EMAIL: &str = "jeanie.f44ky2at@example.net";
+
+    #[tokio::test]
+    async fn followers_should_be_queried_by_publisher() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        for (email, publisher_id) in [(EMAIL, 10001), (EMAIL, 10002), (OTHER_EMAIL, 10002)] {
+            service.follow(req(email, publisher_id)?).await?;
+        }
+        let ret = service.follow(req(EMAIL, 10001)?).await?.into_inner();
+        assert_eq!(ret.publisher_ids, [10001, 10002]);
+
+        assert_eq!(follower_emails(&service, &[10001]).await?, [EMAIL]);
+        let mut emails = follower_emails(&service, &[10001, 10002]).await?;
+        emails.sort();
+        assert_eq!(emails, [EMAIL, OTHER_EMAIL]);
+
+        let ret = service.unfollow(req(EMAIL, 10001)?).await?.into_inner();
+        assert_eq!(ret.publisher_ids, [10002]);
+        assert!(follower_emails(&service, &[10001]).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn follow_should_reject_bad_request() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let err = service.follow(req(EMAIL, 0)?).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        let err = service
+            .follow(req("nobody@example.net", 10001)?)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let err = service
+            .unfollow(req("nobody@example.net", 10001)?)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        Ok(())
+    }
+
+    fn req(email: &str, publisher_id: u32) -> Result<FollowRequest> {
+        Ok(FollowRequestBuilder::default()
+            .email(email)
+            .publisher_id(publisher_id)
+            .build()?)
+    }
+
+    async fn follower_emails(
+        service: &UserStatsService,
+        publisher_ids: &[u32],
+    ) -> Result<Vec<String>> {
+        let query = QueryRequestBuilder::default()
+            .followed_publisher_ids(publisher_ids.to_vec())
+            .include_opted_out(true)
+            .build()?;
+        let stream = service.query(query).await?.into_inner();
+        let emails = stream.map(|user| user.unwrap().email).collect().await;
+        Ok(emails)
+    }
+}
@@ -1,13 +1,24 @@
+mod attribute;
+mod consent;
+mod content;
+mod follow;
+mod watch;
+
+pub(crate) use watch::listen_changes;
+
 use std::collections::HashMap;
 
 use chrono::{DateTime, TimeZone, Utc};
 use prost_types::Timestamp;
-use sqlx::prelude::FromRow;
+use sqlx::{prelude::FromRow, types::Json};
 use tonic::{Response, Status};
 use tracing::info;
 
 use crate::{
-    pb::{IdContent, QueryRequest, QueryRequestBuilder, RawQueryRequest, TimeQuery, User},
+    pb::{
+        Consent, CountResponse, IdContent, QueryRequest, QueryRequestBuilder, RawQueryRequest,
+        TimeQuery, User,
+    },
     ResponseStream, ServiceResult, UserStatsService,
 };
 
@@ -19,33 +30,25 @@ struct UserModel {
     viewed_but_not_started: Vec<i32>,
     started_but_not_finished: Vec<i32>,
     finished: Vec<i32>,
+    email_consent: bool,
+    email_consent_at: Option<DateTime<Utc>>,
+    email_consent_source: Option<String>,
+    sms_consent: bool,
+    sms_consent_at: Option<DateTime<Utc>>,
+    sms_consent_source: Option<String>,
+    in_app_consent: bool,
+    in_app_consent_at: Option<DateTime<Utc>>,
+    in_app_consent_source: Option<String>,
+    attributes: Json<serde_json::Map<String, serde_json::Value>>,
+    locale: String,
 }
 
 impl UserStatsService {
     pub async fn query(&self, query: QueryRequest) -> ServiceResult<ResponseStream> {
         // generate sql based on query
-        let mut sql = "SELECT * FROM user_stats WHERE ".to_string();
-
-        let time_conditions = query
-            .timestamps
-            .into_iter()
-            .map(|(k, v)| timestamp_query(&k, v.lower.as_ref(), v.upper.as_ref()))
-            .collect::<Vec<String>>()
-            .join(" AND ");
-
-        sql.push_str(&time_conditions);
-
-        let id_conditions = query
-            .ids
-            .into_iter()
-            .map(|(k, v)| ids_query(&k, &v.ids))
-            .collect::<Vec<String>>()
-            .join(" AND ");
-
-        if !id_conditions.is_empty() {
-            sql.push_str(" AND ");
-            sql.push_str(&id_conditions);
-        }
+        let conditions =
+            query_conditions(&query).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let sql = format!("SELECT * FROM user_stats WHERE {}", conditions);
 
         // the result list is too long, limit the result for convenience.
         // sql.push_str(" LIMIT 100");
@@ -55,9 +58,24 @@ impl UserStatsService {
         self.raw_query(RawQueryRequest { query: sql }).await
     }
 
+    pub async fn count(&self, query: QueryRequest) -> ServiceResult<CountResponse> {
+        let conditions =
+            query_conditions(&query).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let sql = format!("SELECT count(*) FROM user_stats WHERE {}", conditions);
+
+        let count: i64 = sqlx::query_scalar(&sql)
+            .fetch_one(self.read_pool())
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?;
+
+        Ok(Response::new(CountResponse {
+            count: count as u64,
+        }))
+    }
+
     pub async fn raw_query(&self, req: RawQueryRequest) -> ServiceResult<ResponseStream> {
         let Ok(ret) = sqlx::query_as::<_, UserModel>(&req.query)
-            .fetch_all(&self.pool)
+            .fetch_all(self.read_pool())
             .await
         else {
             return Err(Status::internal(format!(
@@ -93,10 +111,39 @@ impl UserModel {
         );
         contents.insert("finished".to_string(), ids_to_content(self.finished));
 
+        let mut consents = HashMap::new();
+        consents.insert(
+            "email".to_string(),
+            to_consent(
+                self.email_consent,
+                self.email_consent_at,
+                self.email_consent_source,
+            ),
+        );
+        consents.insert(
+            "sms".to_string(),
+            to_consent(
+                self.sms_consent,
+                self.sms_consent_at,
+                self.sms_consent_source,
+            ),
+        );
+        consents.insert(
+            "in_app".to_string(),
+            to_consent(
+                self.in_app_consent,
+                self.in_app_consent_at,
+                self.in_app_consent_source,
+            ),
+        );
+
         User {
             email: self.email,
             name: self.name,
             contents,
+            consents,
+            attributes: attribute::to_attributes(self.attributes.0),
+            locale: self.locale,
         }
     }
 }
@@ -129,6 +176,45 @@ fn ids_to_content(ids: Vec<i32>) -> IdContent {
     }
 }
 
+fn query_conditions(query: &QueryRequest) -> anyhow::Result<String> {
+    let mut conditions = query
+        .timestamps
+        .iter()
+        .map(|(k, v)| timestamp_query(k, v.lower.as_ref(), v.upper.as_ref()))
+        .collect::<Vec<String>>();
+
+    conditions.extend(query.ids.iter().map(|(k, v)| ids_query(k, &v.ids)));
+
+    for (k, v) in query.attributes.iter() {
+        conditions.push(attribute::attribute_query(k, v)?);
+    }
+
+    for (k, v) in query.contents.iter() {
+        conditions.push(content::content_query(k, v)?);
+    }
+
+    if !query.followed_publisher_ids.is_empty() {
+        conditions.push(follow::follow_query(&query.followed_publisher_ids));
+    }
+
+    if !query.include_opted_out {
+        conditions.push(consent::consent_query(query.channel()));
+    }
+
+    if conditions.is_empty() {
+        return Ok("TRUE".to_string());
+    }
+    Ok(conditions.join(" AND "))
+}
+
+fn to_consent(granted: bool, at: Option<DateTime<Utc>>, source: Option<String>) -> Consent {
+    Consent {
+        granted,
+        updated_at: at.map(utc_to_ts),
+        source: source.unwrap_or_default(),
+    }
+}
+
 fn ids_query(name: &str, ids: &[u32]) -> String {
     if ids.is_empty() {
         return "TRUE".to_string();
@@ -164,6 +250,13 @@ fn ts_to_utc(ts: &Timestamp) -> DateTime<Utc> {
     Utc.timestamp_opt(ts.seconds, ts.nanos as _).unwrap()
 }
 
+fn utc_to_ts(dt: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: dt.timestamp(),
+        nanos: dt.timestamp_subsec_nanos() as i32,
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc,
+    },
+    time::sleep,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Response, Status};
+use tracing::warn;
+
+use crate::{pb::QueryRequest, ResponseStream, ServiceResult, UserStatsService};
+
+use super::{query_conditions, UserModel};
+
+const CHANNEL_SIZE: usize = 1024;
+/// postgres channel the user_stats trigger notifies with the changed email
+const CHANGE_CHANNEL: &str = "user_stats_changed";
+/// wait between reconnects of the listener, doubled on every failure in a row
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+impl UserStatsService {
+    pub async fn watch_users(&self, query: QueryRequest) -> ServiceResult<ResponseStream> {
+        let conditions =
+            query_conditions(&query).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let sql = format!(
+            "SELECT * FROM user_stats WHERE {} AND email = $1",
+            conditions
+        );
+
+        let mut changes = self.changes.subscribe();
+        let pool = self.pool.clone();
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            loop {
+                let change = tokio::select! {
+                    change = changes.recv() => change,
+                    // the client is gone, don't wait for a matching change to find out
+                    _ = tx.closed() => break,
+                };
+                let email = match change {
+                    Ok(email) => email,
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("Watcher lagged behind, {} changes skipped", n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                let res = match sqlx::query_as::<_, UserModel>(&sql)
+                    .bind(&email)
+                    .fetch_optional(&pool)
+                    .await
+                {
+                    Ok(Some(user)) => Ok(user.into_user()),
+                    // the changed user does not match the query
+                    Ok(None) => continue,
+                    Err(e) => Err(Status::internal(format!("Database error: {}", e))),
+                };
+
+                // the client is gone
+                if tx.send(res).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let stream = ReceiverStream::new(rx);
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// listen to user_stats changes and broadcast the changed emails to all watchers
+pub(crate) async fn listen_changes(pool: &PgPool) -> sqlx::Result<broadcast::Sender<String>> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANGE_CHANNEL).await?;
+
+    let (tx, _) = broadcast::channel(CHANNEL_SIZE);
+    let sender = tx.clone();
+    tokio::spawn(async move {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match listener.recv().await {
+                // no watcher is not an error
+                Ok(notification) => {
+                    backoff = MIN_BACKOFF;
+                    let _ = sender.send(notification.payload().to_string());
+                }
+                // the listener reconnects on the next recv, changes in between are lost
+                Err(e) => {
+                    warn!(
+                        "Failed to receive user stats changes, retry in {:?}: {}",
+                        backoff, e
+                    );
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    });
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pb::{AttributeQuery, QueryRequestBuilder};
+
+    use super::*;
+    use anyhow::Result;
+    use futures::StreamExt;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn watch_users_should_push_matched_changes() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let query = QueryRequestBuilder::default()
+            .attribute(("tier".to_string(), AttributeQuery::equals("pro")))
+            .build()?;
+        let mut stream = service.watch_users(query).await?.into_inner();
+
+        for (email, tier) in [
+            ("jeanie.f44ky2at@example.net", "free"),
+            ("ettie.yfmn9tqn@example.net", "pro"),
+        ] {
+            sqlx::query("UPDATE user_stats SET attributes = jsonb_build_object('tier', $1::text), locale = 'zh-CN' WHERE email = $2")
+                .bind(tier)
+                .bind(email)
+                .execute(&service.pool)
+                .await?;
+        }
+
+        let user = timeout(Duration::from_secs(5), stream.next())
+            .await?
+            .expect("stream closed")?;
+        assert_eq!(user.email, "ettie.yfmn9tqn@example.net");
+        assert_eq!(user.attributes["tier"], "pro");
+        assert_eq!(user.locale, "zh-CN");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watcher_should_stop_once_the_client_is_gone() -> Result<()> {
+        let (_tpg, service) = UserStatsService::new_for_test().await?;
+        let stream = service
+            .watch_users(QueryRequest::default())
+            .await?
+            .into_inner();
+        assert_eq!(service.changes.receiver_count(), 1);
+
+        // no change is needed for the watcher to notice
+        drop(stream);
+        timeout(Duration::from_secs(5), async {
+            while service.changes.receiver_count() > 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+}
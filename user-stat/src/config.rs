@@ -17,6 +17,19 @@ pub struct AuthConfig {
 pub struct ServerConfig {
     pub port: u16,
     pub db_url: String,
+    /// read replicas for queries, writes always go to db_url
+    #[serde(default)]
+    pub replica_urls: Vec<String>,
+    /// crm-metadata to pull the contents from, otherwise they are only pushed by SyncContents
+    #[serde(default)]
+    pub metadata_url: Option<String>,
+    /// seconds between pulls of the contents from crm-metadata
+    #[serde(default = "default_content_sync_interval")]
+    pub content_sync_interval: u64,
+}
+
+fn default_content_sync_interval() -> u64 {
+    300
 }
 
 impl AppConfig {
@@ -1,18 +1,23 @@
 pub mod abi;
 mod config;
 pub mod pb;
-use std::{ops::Deref, pin::Pin, sync::Arc};
+mod replica;
+use std::{ops::Deref, pin::Pin, sync::Arc, time::Duration};
 
+use crm_metadata::pb::Content;
 use futures::Stream;
 
 use pb::{
     user_stats_server::{UserStats, UserStatsServer},
-    QueryRequest, RawQueryRequest, User,
+    ConsentRequest, CountResponse, FollowRequest, FollowResponse, QueryRequest, RawQueryRequest,
+    SyncContentsResponse, User,
 };
 use sqlx::PgPool;
-use tonic::{Request, Response, Status};
+use tokio::sync::broadcast;
+use tonic::{Request, Response, Status, Streaming};
 
 pub use config::AppConfig;
+use replica::Replicas;
 
 #[derive(Clone)]
 pub struct UserStatsService {
@@ -23,6 +28,8 @@ pub struct UserStatsService {
 pub struct UserStatsServiceInner {
     config: AppConfig,
     pool: PgPool,
+    changes: broadcast::Sender<String>,
+    replicas: Replicas,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
@@ -32,6 +39,7 @@ type ResponseStream = Pin<Box<dyn Stream<Item = Result<User, Status>> + Send>>;
 impl UserStats for UserStatsService {
     type QueryStream = ResponseStream;
     type RawQueryStream = ResponseStream;
+    type WatchUsersStream = ResponseStream;
 
     async fn query(&self, request: Request<QueryRequest>) -> ServiceResult<Self::QueryStream> {
         self.query(request.into_inner()).await
@@ -42,6 +50,40 @@ impl UserStats for UserStatsService {
     ) -> ServiceResult<Self::RawQueryStream> {
         self.raw_query(request.into_inner()).await
     }
+
+    async fn count(&self, request: Request<QueryRequest>) -> ServiceResult<CountResponse> {
+        self.count(request.into_inner()).await
+    }
+
+    async fn opt_in(&self, request: Request<ConsentRequest>) -> ServiceResult<User> {
+        self.opt_in(request.into_inner()).await
+    }
+
+    async fn opt_out(&self, request: Request<ConsentRequest>) -> ServiceResult<User> {
+        self.opt_out(request.into_inner()).await
+    }
+
+    async fn watch_users(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> ServiceResult<Self::WatchUsersStream> {
+        self.watch_users(request.into_inner()).await
+    }
+
+    async fn sync_contents(
+        &self,
+        request: Request<Streaming<Content>>,
+    ) -> ServiceResult<SyncContentsResponse> {
+        self.sync_contents(request.into_inner()).await
+    }
+
+    async fn follow(&self, request: Request<FollowRequest>) -> ServiceResult<FollowResponse> {
+        self.follow(request.into_inner()).await
+    }
+
+    async fn unfollow(&self, request: Request<FollowRequest>) -> ServiceResult<FollowResponse> {
+        self.unfollow(request.into_inner()).await
+    }
 }
 
 impl UserStatsService {
@@ -49,9 +91,31 @@ impl UserStatsService {
         let pool = PgPool::connect(&config.server.db_url)
             .await
             .expect("failed to connect to db");
-        Self {
-            inner: Arc::new(UserStatsServiceInner { config, pool }),
+        let changes = abi::listen_changes(&pool)
+            .await
+            .expect("failed to listen to user stats changes");
+        let replicas = Replicas::connect(&config.server.replica_urls)
+            .await
+            .expect("failed to connect to replicas");
+        let sync = config.server.metadata_url.clone().map(|url| {
+            (
+                url,
+                Duration::from_secs(config.server.content_sync_interval.max(1)),
+            )
+        });
+        let svc = Self {
+            inner: Arc::new(UserStatsServiceInner {
+                config,
+                pool,
+                changes,
+                replicas,
+            }),
+        };
+        if let Some((url, interval)) = sync {
+            svc.spawn_content_sync(&url, interval)
+                .expect("invalid metadata_url");
         }
+        svc
     }
 
     pub fn into_server(self) -> UserStatsServer<Self> {
@@ -59,6 +123,13 @@ impl UserStatsService {
     }
 }
 
+impl UserStatsServiceInner {
+    /// pool for read only queries, a healthy replica if any, otherwise the primary
+    fn read_pool(&self) -> &PgPool {
+        self.replicas.next().unwrap_or(&self.pool)
+    }
+}
+
 impl Deref for UserStatsService {
     type Target = UserStatsServiceInner;
     fn deref(&self) -> &Self::Target {
@@ -87,9 +158,15 @@ pub mod test_utils {
             let server_url = &config.server.db_url[..post];
 
             let (tdb, pool) = get_test_pool(Some(server_url)).await;
+            let changes = crate::abi::listen_changes(&pool).await?;
 
             let svc = Self {
-                inner: Arc::new(UserStatsServiceInner { config, pool }),
+                inner: Arc::new(UserStatsServiceInner {
+                    config,
+                    pool,
+                    changes,
+                    replicas: Default::default(),
+                }),
             };
             Ok((tdb, svc))
         }
@@ -4,10 +4,13 @@ use anyhow::Result;
 
 use futures::StreamExt;
 use sqlx_db_tester::TestPg;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout};
 use tonic::transport::Server;
 use user_stat::{
-    pb::{user_stats_client::UserStatsClient, QueryRequestBuilder, RawQueryRequestBuilder},
+    pb::{
+        user_stats_client::UserStatsClient, Channel, ConsentRequestBuilder, QueryRequestBuilder,
+        RawQueryRequestBuilder,
+    },
     test_utils::{id, tq},
     UserStatsService,
 };
@@ -63,6 +66,51 @@ async fn query_should_work() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn opt_out_should_work() -> Result<()> {
+    let (_tpg, addr) = start_server(PORT_BASE + 2).await?;
+    let mut client = UserStatsClient::connect(format!("http://{addr}")).await?;
+
+    let req = ConsentRequestBuilder::default()
+        .email("ettie.yfmn9tqn@example.net")
+        .channel(Channel::Email as i32)
+        .source("unsubscribe_link")
+        .build()?;
+    let user = client.opt_out(req).await?.into_inner();
+
+    assert!(!user.consents["email"].granted);
+    assert!(user.consents["sms"].granted);
+    assert!(user.consents["in_app"].granted);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_users_should_work() -> Result<()> {
+    let (_tpg, addr) = start_server(PORT_BASE + 3).await?;
+    let mut client = UserStatsClient::connect(format!("http://{addr}")).await?;
+
+    let query = QueryRequestBuilder::default()
+        .channel(Channel::Email as i32)
+        .include_opted_out(true)
+        .build()?;
+    let mut stream = client.watch_users(query).await?.into_inner();
+
+    let req = ConsentRequestBuilder::default()
+        .email("ettie.yfmn9tqn@example.net")
+        .channel(Channel::Email as i32)
+        .build()?;
+    client.opt_out(req).await?;
+
+    let user = timeout(Duration::from_secs(5), stream.next())
+        .await?
+        .expect("stream closed")?;
+    assert_eq!(user.email, "ettie.yfmn9tqn@example.net");
+    assert!(!user.consents["email"].granted);
+
+    Ok(())
+}
+
 async fn start_server(port: u32) -> Result<(TestPg, SocketAddr)> {
     let addr = format!("[::1]:{}", port).parse()?;
 